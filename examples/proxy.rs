@@ -0,0 +1,101 @@
+//! A USI proxy that sits between a GUI and an engine.
+//!
+//! The proxy spawns the engine as a child process. Every line the GUI writes to the
+//! proxy's stdin is parsed as a [`GuiMessage`] and forwarded to the engine. Every line
+//! the engine writes to its stdout is parsed as an [`EngineMessage`] and forwarded to the
+//! proxy's stdout. Messages are re-serialized before forwarding, so the engine and the GUI
+//! only ever see well-formed USI. Anything that parses as `Unknown` is logged to stderr
+//! and dropped.
+//!
+//! The proxy stops after forwarding `quit`, or when the GUI closes stdin (in which case
+//! it sends `quit` to the engine on behalf of the GUI).
+//!
+//! Usage:
+//! ```text
+//! cargo run --example proxy -- <engine> [engine args...]
+//! ```
+use haitaka_usi::*;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+fn main() -> io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let Some(engine) = args.next() else {
+        eprintln!("usage: proxy <engine> [engine args...]");
+        std::process::exit(2);
+    };
+
+    let mut child = Command::new(&engine)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut to_engine = child.stdin.take().expect("engine stdin is piped");
+    let from_engine = child.stdout.take().expect("engine stdout is piped");
+
+    // Engine -> GUI
+    let relay = thread::spawn(move || -> io::Result<()> {
+        let mut to_gui = io::stdout().lock();
+        for line in BufReader::new(from_engine).lines() {
+            let line = format!("{}\n", line?);
+            for msg in EngineMessageStream::new(&line) {
+                match msg {
+                    EngineMessage::Unknown(s) => log_unknown("engine", &s),
+                    msg => writeln!(to_gui, "{msg}")?,
+                }
+            }
+            to_gui.flush()?;
+        }
+        Ok(())
+    });
+
+    // GUI -> Engine
+    let mut quit = false;
+    for line in io::stdin().lock().lines() {
+        let line = format!("{}\n", line?);
+        for msg in GuiMessageStream::new(&line) {
+            match msg {
+                GuiMessage::Unknown(s) => log_unknown("gui", &s),
+                msg => {
+                    quit = msg == GuiMessage::Quit;
+                    if writeln!(to_engine, "{msg}").is_err() {
+                        // the engine went away; nothing left to relay
+                        quit = true;
+                    }
+                }
+            }
+            if quit {
+                break;
+            }
+        }
+        if quit {
+            break;
+        }
+        let _ = to_engine.flush();
+    }
+
+    if !quit {
+        // EOF on stdin: the GUI is gone, so ask the engine to exit
+        let _ = writeln!(to_engine, "{}", GuiMessage::Quit);
+    }
+    let _ = to_engine.flush();
+    drop(to_engine);
+
+    let status = child.wait()?;
+    match relay.join() {
+        Ok(res) => res?,
+        Err(_) => eprintln!("proxy: engine relay thread panicked"),
+    }
+    if !status.success() {
+        eprintln!("proxy: engine exited with {status}");
+    }
+    Ok(())
+}
+
+fn log_unknown(from: &str, s: &str) {
+    // blank lines are not worth reporting
+    if !s.trim().is_empty() {
+        eprintln!("proxy: unknown {from} message: {:?}", s.trim_end());
+    }
+}
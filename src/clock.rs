@@ -0,0 +1,102 @@
+//! This module implements a simple game clock for the USI time controls.
+//!
+//! A GUI sends the remaining times with every "go" command. The [`UsiClock`] keeps
+//! track of those times: it deducts the time used for each move, falls back on byoyomi
+//! once the main time is used up, and adds the increment after each move.
+use crate::gui::EngineParams;
+use haitaka_types::Color;
+use std::time::Duration;
+
+/// The clock state of a game, as sent in "go btime ... wtime ... byoyomi ...".
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// use haitaka_types::Color;
+/// use std::time::Duration;
+///
+/// let mut clock = UsiClock::new();
+/// clock.apply_go(&EngineParams::new().btime(1000).wtime(1000).byoyomi(3000));
+/// assert!(clock.charge(Color::Black, Duration::from_millis(2500)));
+/// assert_eq!(clock.btime, Duration::ZERO);
+/// assert!(!clock.charge(Color::Black, Duration::from_millis(3001)));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UsiClock {
+    /// Black main time left.
+    pub btime: Duration,
+    /// White main time left.
+    pub wtime: Duration,
+    /// Time per move once the main time is used up.
+    pub byoyomi: Duration,
+    /// Black increment, added after each move.
+    pub binc: Duration,
+    /// White increment, added after each move.
+    pub winc: Duration,
+}
+
+impl UsiClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the clock from the time fields of a "go" command.
+    ///
+    /// A "go" command carries the complete clock state, so a missing field is
+    /// loaded as zero.
+    pub fn apply_go(&mut self, params: &EngineParams) {
+        let load = |ms: Option<u64>| Duration::from_millis(ms.unwrap_or(0));
+        self.btime = load(params.btime_ms());
+        self.wtime = load(params.wtime_ms());
+        self.byoyomi = load(params.byoyomi_ms());
+        self.binc = load(params.binc_ms());
+        self.winc = load(params.winc_ms());
+    }
+
+    /// The main time left for `side`.
+    pub fn time(&self, side: Color) -> Duration {
+        match side {
+            Color::Black => self.btime,
+            Color::White => self.wtime,
+        }
+    }
+
+    /// Deduct the time `side` used for a move.
+    ///
+    /// The time is taken from the main time first. Whatever is left over has to fit
+    /// in the byoyomi, which starts afresh with every move. After the move the
+    /// increment of `side` is added to its main time.
+    ///
+    /// Returns false if `side` ran out of time. The main time is then left at zero
+    /// and no increment is added.
+    pub fn charge(&mut self, side: Color, elapsed: Duration) -> bool {
+        let (time, inc) = match side {
+            Color::Black => (&mut self.btime, self.binc),
+            Color::White => (&mut self.wtime, self.winc),
+        };
+        let overrun = elapsed.saturating_sub(*time);
+        *time = time.saturating_sub(elapsed);
+        if overrun > self.byoyomi {
+            return false;
+        }
+        *time += inc;
+        true
+    }
+
+    /// The "go" parameters for the current clock state.
+    ///
+    /// Byoyomi and increments are mutually exclusive in "go", so the increments are
+    /// only included if there is no byoyomi.
+    #[must_use]
+    pub fn go_params(&self) -> EngineParams {
+        let params = EngineParams::new().btime(self.btime).wtime(self.wtime);
+        if !self.byoyomi.is_zero() {
+            params.byoyomi(self.byoyomi)
+        } else if !self.binc.is_zero() || !self.winc.is_zero() {
+            params.binc(self.binc).winc(self.winc)
+        } else {
+            params
+        }
+    }
+}
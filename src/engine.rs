@@ -4,13 +4,40 @@
 //! For full documenation about the protocol see
 //! - [将棋所USIプロトコル](https://shogidokoro2.stars.ne.jp/usi.html)
 //! - [The Universal Shogi Interface](http://hgm.nubati.net/usi.html)
-use crate::format_vec;
-use haitaka_types::Move;
+use crate::helpers::{join_display, join_moves, whole_millis};
+use crate::moves::{MoveList, cmp_moves, move_squares};
+use crate::writer::LineEnding;
+use haitaka_types::{Move, Square};
+use std::cmp::Ordering;
 use std::fmt;
 use std::time::Duration;
 
+/// The centipawn value of a mate score, see [`InfoParam::score_ordering_key`].
+///
+/// This is the convention of many shogi engines, which report a mate in `n` plies
+/// as `MATE_SCORE_CP - n`.
+pub const MATE_SCORE_CP: i32 = 32000;
+
 /// Messages sent from the Shogi Engine to the GUI.
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+///
+/// Messages are ordered by variant, in declaration order, and then by contents. The
+/// order is stable, so it can be used to sort messages in tests or to put them in a
+/// `BTreeSet`.
+///
+/// The enum is `#[non_exhaustive]`, since protocol extensions may add variants. A
+/// `match` needs a wildcard arm, or use [`EngineMessage::kind`] and the `is_*` methods.
+///
+/// ```
+/// use haitaka_usi::*;
+/// let msg = EngineMessage::parse("bestmove 7g7f\n").unwrap();
+/// let done = match msg {
+///     EngineMessage::BestMove(_) => true,
+///     _ => false,
+/// };
+/// assert!(done && msg.is_bestmove());
+/// ```
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+#[non_exhaustive]
 pub enum EngineMessage {
     /// `id` - the `id` message informs the GUI about the engine name and engine
     /// developer. This message is sent as initial response to the GUI `usi` message.
@@ -117,7 +144,11 @@ pub enum EngineMessage {
 }
 
 /// Represents content of "id" message ("id name..." or "id author ...").
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+///
+/// The value is the rest of the line. A line like `id name Foo author Bar`, as sent
+/// by some buggy engines, is therefore parsed as the name "Foo author Bar". Since
+/// names may contain any words, the parser does not try to split it.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub enum IdParams {
     Name(String),
     Author(String),
@@ -125,6 +156,7 @@ pub enum IdParams {
 
 /// Represents payload of "bestmove" message.
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
+#[non_exhaustive]
 pub enum BestMoveParams {
     BestMove {
         bestmove: Move,
@@ -132,13 +164,19 @@ pub enum BestMoveParams {
     },
     Win,
     Resign,
+
+    /// The engine has no move (`bestmove 0000` or `bestmove (none)`).
+    ///
+    /// This is an extension, not part of the USI spec, see
+    /// [`crate::ParseOptions::no_move_tokens`]. It is displayed as `0000`.
+    None,
 }
 
 /// Represents payload of "checkmate" message, sent after a "go mate" search terminates.
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub enum CheckMateParams {
     /// Main line of checkmate solution
-    Mate(Vec<Move>),
+    Mate(MoveList),
     /// No forced mate exists
     NoMate,
     /// Search for a forced mate timed out and was inconclusive
@@ -148,7 +186,7 @@ pub enum CheckMateParams {
 }
 
 /// Represents copy protection or registration state.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
 pub enum StatusCheck {
     /// Signifies the engine is checking the copy protection or registration.
     Checking,
@@ -161,7 +199,8 @@ pub enum StatusCheck {
 }
 
 /// Represents contents of the "option" message.
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+#[non_exhaustive]
 pub enum OptionParam {
     Check {
         name: String,
@@ -192,8 +231,39 @@ pub enum OptionParam {
     },
 }
 
+impl OptionParam {
+    /// The name of the option.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Check { name, .. }
+            | Self::Spin { name, .. }
+            | Self::Combo { name, .. }
+            | Self::Button { name }
+            | Self::String { name, .. }
+            | Self::Filename { name, .. } => name,
+        }
+    }
+
+    /// The option type, as used in the protocol (`check`, `spin`, `combo`, `button`,
+    /// `string` or `filename`).
+    pub fn type_str(&self) -> &'static str {
+        match self {
+            Self::Check { .. } => "check",
+            Self::Spin { .. } => "spin",
+            Self::Combo { .. } => "combo",
+            Self::Button { .. } => "button",
+            Self::String { .. } => "string",
+            Self::Filename { .. } => "filename",
+        }
+    }
+}
+
 /// Represents possible payloads of the "info" message.
+///
+/// Numbers that are too large for their field (`info depth 70000`) are clamped to
+/// the maximum value of the field type.
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
+#[non_exhaustive]
 pub enum InfoParam {
     /// The `info depth` message. Search depth in plies.
     Depth(u16),
@@ -203,13 +273,18 @@ pub enum InfoParam {
     SelDepth(u16),
 
     /// The `info time` message. The time searched. Should be sent with the pv.
+    /// Since the wire format is in milliseconds, the time is serialized rounded to
+    /// the nearest millisecond (see [`crate::round_to_millis`]).
     Time(Duration),
 
     /// The `info nodes` message. Number of nodes searched.
     Nodes(u64),
 
     /// The `info pv` message (principal variation, best line).
-    Pv(Vec<Move>),
+    ///
+    /// Some engines send an empty `pv` as a placeholder (`info depth 1 pv`). The parser
+    /// drops an empty `pv`, and the `Display` of an `info` message leaves it out.
+    Pv(MoveList),
 
     /// The `info pv ... multipv` message (the pv line number in a multi pv sequence).
     MultiPv(u16),
@@ -233,26 +308,43 @@ pub enum InfoParam {
     /// The `info nps` message (nodes per second).
     Nps(u64),
 
+    /// The `info tbhits` message (number of positions found in endgame tablebases).
+    /// This is an extension, not part of the USI spec.
+    TbHits(u64),
+
+    /// The `info sbhits` message (number of positions found in shredderbases).
+    /// This is an extension, not part of the USI spec.
+    SbHits(u64),
+
     /// The `info cpuload` message (CPU load in permills).
     CpuLoad(u16),
 
     /// The `info string` message (a string the GUI should display).
+    ///
+    /// Info strings must be single-line on the wire, so `Display` replaces any
+    /// embedded `\n` or `\r` with a space.
     String(String),
 
     /// The `info refutation` message (the first move is the move being refuted).
-    Refutation(Vec<Move>),
+    Refutation(MoveList),
 
     /// The `info currline` message (current line being calculated on a CPU).
     CurrLine {
-        /// The CPU number calculating this line.
+        /// The CPU number calculating this line. This is `None` if the engine did not
+        /// send one, which by convention means CPU 1 (see [`InfoParam::cpu_or_default`]).
         cpu_nr: Option<u16>,
 
         /// The line being calculated.
-        line: Vec<Move>,
+        line: MoveList,
     },
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+/// The bound of an `info score` value, or the sign of a bare `score mate`.
+///
+/// Displayed as the protocol token without surrounding whitespace (`Exact` is
+/// displayed as the empty string).
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+#[non_exhaustive]
 pub enum ScoreBound {
     MatePlus,
     MateMin,
@@ -261,8 +353,257 @@ pub enum ScoreBound {
     Upper,
 }
 
+impl EngineMessage {
+    /// Compare two messages for semantic equality.
+    ///
+    /// This is the same as `==`, except that the order of the `info` params is
+    /// treated as insignificant. Two `Info` messages are semantically equal if they
+    /// contain the same params the same number of times, in any order.
+    ///
+    /// Differences in whitespace between two parsed messages are already normalized
+    /// away by the parser, so they never affect equality.
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (EngineMessage::Info(v1), EngineMessage::Info(v2)) => {
+                if v1.len() != v2.len() {
+                    return false;
+                }
+                let mut used = vec![false; v2.len()];
+                v1.iter().all(|p1| {
+                    if let Some(i) = (0..v2.len()).find(|&i| !used[i] && v2[i] == *p1) {
+                        used[i] = true;
+                        true
+                    } else {
+                        false
+                    }
+                })
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Serialize the message as a newline-terminated protocol line.
+    pub fn to_usi_line(&self) -> String {
+        self.to_usi_line_with(LineEnding::Lf)
+    }
+
+    /// Serialize the message as a protocol line with the given line ending.
+    pub fn to_usi_line_with(&self, line_ending: LineEnding) -> String {
+        format!("{}{}", self, line_ending)
+    }
+
+    /// Is this an `id` message?
+    pub fn is_id(&self) -> bool {
+        matches!(self, Self::Id(_))
+    }
+
+    /// Is this a `usiok` message?
+    pub fn is_usiok(&self) -> bool {
+        matches!(self, Self::UsiOk)
+    }
+
+    /// Is this a `readyok` message?
+    pub fn is_readyok(&self) -> bool {
+        matches!(self, Self::ReadyOk)
+    }
+
+    /// Is this a `bestmove` message?
+    pub fn is_bestmove(&self) -> bool {
+        matches!(self, Self::BestMove(_))
+    }
+
+    /// Is this a `checkmate` message?
+    pub fn is_checkmate(&self) -> bool {
+        matches!(self, Self::CheckMate(_))
+    }
+
+    /// Is this a `copyprotection` message?
+    pub fn is_copyprotection(&self) -> bool {
+        matches!(self, Self::CopyProtection(_))
+    }
+
+    /// Is this a `registration` message?
+    pub fn is_registration(&self) -> bool {
+        matches!(self, Self::Registration(_))
+    }
+
+    /// Is this an `option` message?
+    pub fn is_option(&self) -> bool {
+        matches!(self, Self::Option(_))
+    }
+
+    /// Is this an `info` message?
+    pub fn is_info(&self) -> bool {
+        matches!(self, Self::Info(_))
+    }
+
+    /// Is this an `info` message that reports on a search?
+    ///
+    /// This is false for an `info` that only carries an `info string`, which some
+    /// engines send while still initializing, before any `go`.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let progress = EngineMessage::parse("info string loading eval\n").unwrap();
+    /// assert!(progress.is_info() && !progress.is_search_info());
+    /// let search = EngineMessage::parse("info depth 3 string hello\n").unwrap();
+    /// assert!(search.is_search_info());
+    /// ```
+    pub fn is_search_info(&self) -> bool {
+        match self {
+            Self::Info(params) => params.iter().any(|p| !matches!(p, InfoParam::String(_))),
+            _ => false,
+        }
+    }
+
+    /// The `pv` of an `info` message, paired with its `multipv` index.
+    ///
+    /// The index defaults to 1 if the message has no `multipv`. Returns `None` if this
+    /// is not an `info` message with a `pv`.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = EngineMessage::parse("info multipv 2 pv 7g7f 3c3d\n").unwrap();
+    /// let (index, pv) = msg.pv_with_index().unwrap();
+    /// assert_eq!((index, pv.len()), (2, 2));
+    /// ```
+    pub fn pv_with_index(&self) -> Option<(u16, &[Move])> {
+        let Self::Info(params) = self else {
+            return None;
+        };
+        let mut index = 1;
+        let mut pv = None;
+        for param in params {
+            match param {
+                InfoParam::MultiPv(n) => index = *n,
+                InfoParam::Pv(mvs) => pv = Some(mvs.as_slice()),
+                _ => {}
+            }
+        }
+        Some((index, pv?))
+    }
+
+    /// The `currmove` of an `info` message, paired with its `currmovenumber`.
+    ///
+    /// The number is `None` if the message has no `currmovenumber`. Returns `None` if
+    /// this is not an `info` message with a `currmove`, even if it has a
+    /// `currmovenumber`.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = EngineMessage::parse("info currmove 2g2f currmovenumber 1\n").unwrap();
+    /// assert_eq!(msg.current_move(), Some(("2g2f".parse().unwrap(), Some(1))));
+    /// ```
+    pub fn current_move(&self) -> Option<(Move, Option<u16>)> {
+        let Self::Info(params) = self else {
+            return None;
+        };
+        let mut currmove = None;
+        let mut number = None;
+        for param in params {
+            match param {
+                InfoParam::CurrMove(mv) => currmove = Some(*mv),
+                InfoParam::CurrMoveNumber(n) => number = Some(*n),
+                _ => {}
+            }
+        }
+        Some((currmove?, number))
+    }
+
+    /// The ratio of the reported `nps` to the nps computed from `nodes` and `time`.
+    ///
+    /// Returns `None` if this is not an `info` message with all three fields, or if
+    /// `time` or `nodes` is zero. A value far from 1.0 means the engine's reported
+    /// nps is inconsistent with its own node count.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = EngineMessage::parse("info time 2000 nodes 100000 nps 100000\n").unwrap();
+    /// assert_eq!(msg.info_nps_consistency(), Some(2.0));
+    /// ```
+    pub fn info_nps_consistency(&self) -> Option<f64> {
+        let Self::Info(params) = self else {
+            return None;
+        };
+        let (mut time, mut nodes, mut nps) = (None, None, None);
+        for param in params {
+            match param {
+                InfoParam::Time(t) => time = Some(*t),
+                InfoParam::Nodes(n) => nodes = Some(*n),
+                InfoParam::Nps(n) => nps = Some(*n),
+                _ => {}
+            }
+        }
+        let secs = time?.as_secs_f64();
+        let nodes = nodes?;
+        if secs == 0.0 || nodes == 0 {
+            return None;
+        }
+        Some(nps? as f64 / (nodes as f64 / secs))
+    }
+
+    /// Render the message for logging, truncating long move lists.
+    ///
+    /// Any `pv`, `refutation` or `currline` move list in an `info` message is cut
+    /// after `max_pv` moves and followed by `...(+N)`, where N is the number of
+    /// moves left out. Other messages are rendered as by `Display`.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = EngineMessage::parse("info depth 2 pv 7g7f 3c3d 2g2f\n").unwrap();
+    /// assert_eq!(msg.summarize(1), "info depth 2 pv 7g7f ...(+2)");
+    /// ```
+    pub fn summarize(&self, max_pv: usize) -> String {
+        let Self::Info(params) = self else {
+            return self.to_string();
+        };
+        let params: Vec<String> = params
+            .iter()
+            .map(|param| match param {
+                InfoParam::Pv(mvs) => format!("pv {}", truncate_moves(mvs, max_pv)),
+                InfoParam::Refutation(mvs) => {
+                    format!("refutation {}", truncate_moves(mvs, max_pv))
+                }
+                InfoParam::CurrLine {
+                    cpu_nr: Some(cpu_nr),
+                    line,
+                } => format!("currline {} {}", cpu_nr, truncate_moves(line, max_pv)),
+                InfoParam::CurrLine { cpu_nr: None, line } => {
+                    format!("currline {}", truncate_moves(line, max_pv))
+                }
+                _ => param.to_string(),
+            })
+            .collect();
+        format!("info {}", params.join(" "))
+    }
+
+    /// Is this a message that does not conform to the protocol?
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+
+    /// The command keyword of the message (`"bestmove"`, `"info"`, ...), or `"unknown"`.
+    ///
+    /// This is meant for keying metrics or logs by message type.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Id(_) => "id",
+            Self::UsiOk => "usiok",
+            Self::ReadyOk => "readyok",
+            Self::BestMove(_) => "bestmove",
+            Self::CheckMate(_) => "checkmate",
+            Self::CopyProtection(_) => "copyprotection",
+            Self::Registration(_) => "registration",
+            Self::Option(_) => "option",
+            Self::Info(_) => "info",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
 // Note that the Display for EngineMessage does not add a terminating newline character.
-// When actually sending protocol messages a writer should add the '\n'.
+// When actually sending protocol messages a writer should add the '\n'
+// (or use `to_usi_line`).
 
 impl fmt::Display for EngineMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -273,9 +614,18 @@ impl fmt::Display for EngineMessage {
             EngineMessage::BestMove(params) => write!(f, "bestmove {}", params),
             EngineMessage::CheckMate(params) => write!(f, "checkmate {}", params),
             EngineMessage::CopyProtection(state) => write!(f, "copyprotection {}", state),
-            EngineMessage::Registration(state) => write!(f, "register {}", state),
+            EngineMessage::Registration(state) => write!(f, "registration {}", state),
             EngineMessage::Option(option) => write!(f, "option {}", option),
-            EngineMessage::Info(info) => write!(f, "info {}", format_vec!(info)),
+            EngineMessage::Info(info) => {
+                let info: Vec<&InfoParam> = info
+                    .iter()
+                    .filter(|param| !matches!(param, InfoParam::Pv(mvs) if mvs.is_empty()))
+                    .collect();
+                if info.is_empty() {
+                    return write!(f, "info");
+                }
+                write!(f, "info {}", join_display(&info, " "))
+            }
             EngineMessage::Unknown(s) => write!(f, "UNKNOWN \"{}\"", s),
         }
     }
@@ -299,6 +649,7 @@ impl fmt::Display for BestMoveParams {
             },
             Self::Win => write!(f, "win"),
             Self::Resign => write!(f, "resign"),
+            Self::None => write!(f, "0000"),
         }
     }
 }
@@ -306,10 +657,10 @@ impl fmt::Display for BestMoveParams {
 impl fmt::Display for CheckMateParams {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Mate(mvs) => write!(f, "{}", format_vec!(mvs)),
+            Self::Mate(mvs) => write!(f, "{}", mvs),
             Self::NoMate => write!(f, "nomate"),
             Self::TimeOut => write!(f, "timeout"),
-            _ => write!(f, "notimplemented"),
+            Self::NotImplemented => write!(f, "notimplemented"),
         }
     }
 }
@@ -373,7 +724,7 @@ impl fmt::Display for OptionParam {
                     opt += &format!(" default {}", default);
                 }
                 if !vars.is_empty() {
-                    opt += &format!(" var {}", format_vec!(vars, " var "));
+                    opt += &format!(" var {}", join_display(vars, " var "));
                 }
                 write!(f, "{}", opt)
             }
@@ -381,50 +732,289 @@ impl fmt::Display for OptionParam {
     }
 }
 
+impl InfoParam {
+    /// The keyword of the param (`"depth"`, `"pv"`, ...). Both score params give `"score"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Depth(_) => "depth",
+            Self::SelDepth(_) => "seldepth",
+            Self::Time(_) => "time",
+            Self::Nodes(_) => "nodes",
+            Self::Pv(_) => "pv",
+            Self::MultiPv(_) => "multipv",
+            Self::ScoreCp(..) | Self::ScoreMate(..) => "score",
+            Self::CurrMove(_) => "currmove",
+            Self::CurrMoveNumber(_) => "currmovenumber",
+            Self::HashFull(_) => "hashfull",
+            Self::Nps(_) => "nps",
+            Self::TbHits(_) => "tbhits",
+            Self::SbHits(_) => "sbhits",
+            Self::CpuLoad(_) => "cpuload",
+            Self::String(_) => "string",
+            Self::Refutation(_) => "refutation",
+            Self::CurrLine { .. } => "currline",
+        }
+    }
+
+    /// The CPU number of an `info currline` param, defaulting to 1 if it was not sent.
+    ///
+    /// Engines running on a single CPU usually leave out the CPU number, and CPUs are
+    /// numbered from 1, so `currline 7g7f` and `currline 1 7g7f` are treated alike.
+    /// Returns `None` for all other params.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let EngineMessage::Info(info) = EngineMessage::parse("info currline 7g7f\n").unwrap() else {
+    ///     panic!();
+    /// };
+    /// assert_eq!(info[0].cpu_or_default(), Some(1));
+    /// ```
+    pub fn cpu_or_default(&self) -> Option<u16> {
+        match self {
+            Self::CurrLine { cpu_nr, .. } => Some(cpu_nr.unwrap_or(1)),
+            _ => None,
+        }
+    }
+
+    /// The time of an `info time` param in whole milliseconds, as emitted by `Display`.
+    ///
+    /// Returns `None` for all other params.
+    pub fn time_ms(&self) -> Option<u64> {
+        match self {
+            Self::Time(t) => Some(whole_millis(*t)),
+            _ => None,
+        }
+    }
+
+    /// The score of an `info score` param on one centipawn scale.
+    ///
+    /// A `score cp` gives its value. A mate in `n` plies gives `MATE_SCORE_CP - n`, and
+    /// being mated in `n` plies gives `-MATE_SCORE_CP + n`, so that a faster mate
+    /// ranks higher (see [`MATE_SCORE_CP`]). The number of plies is clamped to
+    /// `1..MATE_SCORE_CP`, so that a mate keeps its sign however long it is. `score mate 0`
+    /// (the side to move is mated) gives `-MATE_SCORE_CP`. A bare `score mate +` or
+    /// `score mate -` gives `MATE_SCORE_CP` or `-MATE_SCORE_CP`. The bound is ignored.
+    ///
+    /// Returns `None` for all other params.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// assert_eq!(InfoParam::ScoreCp(-50, ScoreBound::Exact).score_ordering_key(), Some(-50));
+    /// assert_eq!(InfoParam::ScoreMate(Some(3), ScoreBound::Exact).score_ordering_key(), Some(31997));
+    /// assert_eq!(InfoParam::ScoreMate(Some(-4), ScoreBound::Exact).score_ordering_key(), Some(-31996));
+    /// ```
+    pub fn score_ordering_key(&self) -> Option<i32> {
+        match self {
+            Self::ScoreCp(cp, _) => Some(*cp),
+            Self::ScoreMate(Some(0), _) => Some(-MATE_SCORE_CP),
+            Self::ScoreMate(Some(n), _) => {
+                let plies = n.unsigned_abs().min(MATE_SCORE_CP as u32 - 1) as i32;
+                Some(n.signum() * (MATE_SCORE_CP - plies))
+            }
+            Self::ScoreMate(None, ScoreBound::MateMin) => Some(-MATE_SCORE_CP),
+            Self::ScoreMate(None, _) => Some(MATE_SCORE_CP),
+            _ => None,
+        }
+    }
+
+    /// The source and target square of an `info currmove` param, see [`move_squares`].
+    ///
+    /// Returns `None` for all other params.
+    pub fn currmove_squares(&self) -> Option<(Option<Square>, Square)> {
+        match self {
+            Self::CurrMove(mv) => Some(move_squares(mv)),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for InfoParam {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Depth(n) => write!(f, "depth {}", n),
             Self::SelDepth(n) => write!(f, "seldepth {}", n),
-            Self::Time(n) => write!(f, "time {}", n.as_millis()),
+            Self::Time(n) => write!(f, "time {}", whole_millis(*n)),
             Self::Nodes(n) => write!(f, "nodes {}", n),
-            Self::Pv(mvs) => write!(f, "pv {}", format_vec!(mvs)),
+            Self::Pv(mvs) => write!(f, "pv {}", mvs),
             Self::MultiPv(n) => write!(f, "multipv {}", n),
-            Self::ScoreCp(cp, bound) => write!(f, "score cp {}{}", cp, bound),
-            Self::ScoreMate(plies, bound) => {
-                if let Some(plies) = plies {
-                    write!(f, "score mate {}{}", plies, bound)
-                } else {
-                    debug_assert!(*bound == ScoreBound::MateMin || *bound == ScoreBound::MatePlus);
-                    write!(f, "score mate{}", bound)
-                }
+            Self::ScoreCp(cp, ScoreBound::Exact) => write!(f, "score cp {}", cp),
+            Self::ScoreCp(cp, bound) => write!(f, "score cp {} {}", cp, bound),
+            Self::ScoreMate(Some(plies), ScoreBound::Exact) => write!(f, "score mate {}", plies),
+            Self::ScoreMate(Some(plies), bound) => write!(f, "score mate {} {}", plies, bound),
+            Self::ScoreMate(None, bound) => {
+                debug_assert!(*bound == ScoreBound::MateMin || *bound == ScoreBound::MatePlus);
+                write!(f, "score mate {}", bound)
             }
             Self::CurrMove(mv) => write!(f, "currmove {}", mv),
             Self::CurrMoveNumber(n) => write!(f, "currmovenumber {}", n),
             Self::HashFull(n) => write!(f, "hashfull {}", n),
             Self::Nps(n) => write!(f, "nps {}", n),
+            Self::TbHits(n) => write!(f, "tbhits {}", n),
+            Self::SbHits(n) => write!(f, "sbhits {}", n),
             Self::CpuLoad(n) => write!(f, "cpuload {}", n),
-            Self::String(s) => write!(f, "string {}", s),
-            Self::Refutation(mvs) => write!(f, "refutation {}", format_vec!(mvs)),
+            Self::String(s) => write!(f, "string {}", s.replace(['\n', '\r'], " ")),
+            Self::Refutation(mvs) => write!(f, "refutation {}", mvs),
             Self::CurrLine { cpu_nr, line } => {
                 if let Some(cpu_nr) = cpu_nr {
-                    write!(f, "currline {} {}", cpu_nr, format_vec!(line))
+                    write!(f, "currline {} {}", cpu_nr, line)
                 } else {
-                    write!(f, "currline {}", format_vec!(line))
+                    write!(f, "currline {}", line)
                 }
             }
         }
     }
 }
 
+fn truncate_moves(mvs: &[Move], max: usize) -> String {
+    if mvs.len() <= max {
+        return join_moves(mvs);
+    }
+    let omitted = format!("...(+{})", mvs.len() - max);
+    if max == 0 {
+        omitted
+    } else {
+        format!("{} {}", join_moves(&mvs[..max]), omitted)
+    }
+}
+
+// Messages are ordered by variant (in declaration order), then by contents. Since
+// `Move` does not implement `Ord`, the types which hold a bare `Move` implement the
+// ordering by hand, using `cmp_moves`.
+
+fn cmp_optional_moves(a: &Option<Move>, b: &Option<Move>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp_moves(a, b),
+        _ => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+impl BestMoveParams {
+    /// The source and target square of the best move, see [`move_squares`].
+    ///
+    /// Returns `None` if there is no move (`resign`, `win`, or no move at all).
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let EngineMessage::BestMove(params) = EngineMessage::parse("bestmove 7g7f\n").unwrap() else {
+    ///     panic!();
+    /// };
+    /// assert_eq!(params.squares(), Some((Some(Square::G7), Square::F7)));
+    /// ```
+    pub fn squares(&self) -> Option<(Option<Square>, Square)> {
+        match self {
+            Self::BestMove { bestmove, .. } => Some(move_squares(bestmove)),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::BestMove { .. } => 0,
+            Self::Win => 1,
+            Self::Resign => 2,
+            Self::None => 3,
+        }
+    }
+}
+
+impl Ord for BestMoveParams {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (
+                Self::BestMove {
+                    bestmove: b1,
+                    ponder: p1,
+                },
+                Self::BestMove {
+                    bestmove: b2,
+                    ponder: p2,
+                },
+            ) => cmp_moves(b1, b2).then_with(|| cmp_optional_moves(p1, p2)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for BestMoveParams {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl InfoParam {
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Depth(_) => 0,
+            Self::SelDepth(_) => 1,
+            Self::Time(_) => 2,
+            Self::Nodes(_) => 3,
+            Self::Pv(_) => 4,
+            Self::MultiPv(_) => 5,
+            Self::ScoreCp(..) => 6,
+            Self::ScoreMate(..) => 7,
+            Self::CurrMove(_) => 8,
+            Self::CurrMoveNumber(_) => 9,
+            Self::HashFull(_) => 10,
+            Self::Nps(_) => 11,
+            Self::TbHits(_) => 12,
+            Self::SbHits(_) => 13,
+            Self::CpuLoad(_) => 14,
+            Self::String(_) => 15,
+            Self::Refutation(_) => 16,
+            Self::CurrLine { .. } => 17,
+        }
+    }
+}
+
+impl Ord for InfoParam {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Depth(a), Self::Depth(b)) => a.cmp(b),
+            (Self::SelDepth(a), Self::SelDepth(b)) => a.cmp(b),
+            (Self::Time(a), Self::Time(b)) => a.cmp(b),
+            (Self::Nodes(a), Self::Nodes(b)) => a.cmp(b),
+            (Self::Pv(a), Self::Pv(b)) => a.cmp(b),
+            (Self::MultiPv(a), Self::MultiPv(b)) => a.cmp(b),
+            (Self::ScoreCp(a, x), Self::ScoreCp(b, y)) => (a, x).cmp(&(b, y)),
+            (Self::ScoreMate(a, x), Self::ScoreMate(b, y)) => (a, x).cmp(&(b, y)),
+            (Self::CurrMove(a), Self::CurrMove(b)) => cmp_moves(a, b),
+            (Self::CurrMoveNumber(a), Self::CurrMoveNumber(b)) => a.cmp(b),
+            (Self::HashFull(a), Self::HashFull(b)) => a.cmp(b),
+            (Self::Nps(a), Self::Nps(b)) => a.cmp(b),
+            (Self::TbHits(a), Self::TbHits(b)) => a.cmp(b),
+            (Self::SbHits(a), Self::SbHits(b)) => a.cmp(b),
+            (Self::CpuLoad(a), Self::CpuLoad(b)) => a.cmp(b),
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Refutation(a), Self::Refutation(b)) => a.cmp(b),
+            (
+                Self::CurrLine {
+                    cpu_nr: c1,
+                    line: l1,
+                },
+                Self::CurrLine {
+                    cpu_nr: c2,
+                    line: l2,
+                },
+            ) => (c1, l1).cmp(&(c2, l2)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for InfoParam {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for ScoreBound {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::Lower => write!(f, " lowerbound"),
-            Self::Upper => write!(f, " upperbound"),
-            Self::MateMin => write!(f, " -"),
-            Self::MatePlus => write!(f, " +"),
-            _ => write!(f, ""),
+            Self::Lower => write!(f, "lowerbound"),
+            Self::Upper => write!(f, "upperbound"),
+            Self::MateMin => write!(f, "-"),
+            Self::MatePlus => write!(f, "+"),
+            Self::Exact => write!(f, ""),
         }
     }
 }
@@ -0,0 +1,35 @@
+//! This module implements the extraction of an evaluation graph from the `info`
+//! lines of a search.
+use crate::engine::{EngineMessage, InfoParam};
+
+/// The `(depth, score)` points of successive `info` lines, for plotting.
+///
+/// Each `info` message with both a `depth` and a `score` gives one point. Mate scores
+/// are mapped onto the centipawn scale by [`InfoParam::score_ordering_key`]. All other
+/// messages are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let input = "info depth 1 score cp 20\ninfo nodes 100\ninfo depth 2 score mate 5\nbestmove 7g7f\n";
+/// let points = eval_series(EngineMessageStream::new(input));
+/// assert_eq!(points, [(1, 20), (2, MATE_SCORE_CP - 5)]);
+/// ```
+pub fn eval_series(messages: impl IntoIterator<Item = EngineMessage>) -> Vec<(u16, i32)> {
+    let mut points = Vec::new();
+    for msg in messages {
+        let EngineMessage::Info(params) = msg else {
+            continue;
+        };
+        let depth = params.iter().find_map(|param| match param {
+            InfoParam::Depth(d) => Some(*d),
+            _ => None,
+        });
+        let score = params.iter().find_map(InfoParam::score_ordering_key);
+        if let (Some(depth), Some(score)) = (depth, score) {
+            points.push((depth, score));
+        }
+    }
+    points
+}
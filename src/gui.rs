@@ -4,16 +4,25 @@
 //! For full documenation about the protocol see
 //! - [将棋所USIプロトコル](https://shogidokoro2.stars.ne.jp/usi.html)
 //! - [The Universal Shogi Interface](http://hgm.nubati.net/usi.html)
-use crate::format_vec;
-use crate::helpers::IntoDuration;
-use haitaka_types::Move;
+use crate::helpers::{IntoDuration, whole_millis};
+use crate::moves::MoveList;
+use crate::writer::LineEnding;
+use haitaka_types::{Color, Move};
 use std::fmt;
 use std::time::Duration;
 
 pub const SFEN_STARTPOS: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
 
 /// Messages sent from the GUI to the engine.
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+///
+/// Messages are ordered by variant, in declaration order, and then by contents. The
+/// order is stable, so it can be used to sort messages in tests or to put them in a
+/// `BTreeSet`.
+///
+/// The enum is `#[non_exhaustive]`, since protocol extensions may add variants. A
+/// `match` needs a wildcard arm, or use [`GuiMessage::kind`] and the `is_*` methods.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+#[non_exhaustive]
 pub enum GuiMessage {
     /// `usi` - the first command sent to the engine to start the USI protocol.
     /// The engine should respond to this handshake with
@@ -45,6 +54,9 @@ pub enum GuiMessage {
     /// setoption name <option_name>
     /// setoption name <option_name> value <option_value>
     /// ```
+    /// The value is the remainder of the line after `value`, and may contain spaces
+    /// (for instance file paths). Only leading and trailing whitespace is trimmed.
+    /// As in `option ... default <empty>`, the value `<empty>` stands for the empty string.
     SetOption { name: String, value: Option<String> },
 
     /// `register` - registers the user to the engine. This is only required if the
@@ -76,7 +88,7 @@ pub enum GuiMessage {
     /// [`haitaka::board::Board::from_sfen`](https://docs.rs/haitaka/0.2.2/haitaka/board/struct.Board.html#method.from_sfen).
     Position {
         sfen: Option<String>,
-        moves: Option<Vec<Move>>,
+        moves: Option<MoveList>,
     },
 
     /// `go` - tells the engine to start its search for the best move, given the position.
@@ -128,7 +140,11 @@ pub enum GuiMessage {
 ///
 /// Informs the engine that the game has ended with the specified result,
 /// from the engine's own point or view.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///
+/// There is no variant for a missing or unrecognized result. A bare `gameover`
+/// or `gameover <something else>` is not a valid protocol message and is parsed
+/// as `GuiMessage::Unknown`, unless it is listed in [`crate::ParseOptions::gameover_synonyms`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum GameStatus {
     Win,
     Lose,
@@ -139,10 +155,10 @@ pub enum GameStatus {
 ///
 /// Multiple parameters will and should be set in one "go" command.
 ///
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EngineParams {
     /// Restrict search to these moves only.
-    searchmoves: Option<Vec<Move>>,
+    searchmoves: Option<MoveList>,
 
     /// Start search in ponder mode.
     ponder: bool,
@@ -153,14 +169,19 @@ pub struct EngineParams {
     /// White time left (ms).
     wtime: Option<Duration>,
 
-    /// Black time increment per move (if greater than 0), a small amount of time
-    /// added to the clock after each move (the "Fischer increment"). This is not
-    /// used in combination with `byoyomi`.
+    /// Black time increment per move, a small amount of time added to the clock
+    /// after each move (the "Fischer increment"). This is not used in combination
+    /// with `byoyomi`.
+    ///
+    /// The spec says increments are only sent if greater than 0, but some GUIs send
+    /// `binc 0` anyway. An explicit zero is kept as `Some(Duration::ZERO)` (and not
+    /// normalized to `None`), so that the message round-trips exactly. The same
+    /// applies to `winc`.
     binc: Option<Duration>,
 
-    /// White time increment per move (if greater than 0), a small amount of time
-    /// added to the clock after each move (the "Fischer increment"). This is not
-    /// used in combination with `byoyomi`.
+    /// White time increment per move, a small amount of time added to the clock
+    /// after each move (the "Fischer increment"). This is not used in combination
+    /// with `byoyomi`.
     winc: Option<Duration>,
 
     /// Amount of time (ms) that each player is allowed per move after running out of time.
@@ -187,25 +208,322 @@ pub struct EngineParams {
 }
 
 /// Mate paramater representing the "go mate x" command.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///
+/// The meaning of a numeric argument is ambiguous. The USI spec (as documented by
+/// Shogidokoro, and as implemented by most shogi GUIs and engines) defines it as a time
+/// limit in milliseconds, which is the default interpretation. In UCI, from which USI
+/// was derived, it is the number of moves to search a mate in, and some tools ported
+/// from chess still send it with that meaning. Set [`crate::ParseOptions::mate_in_moves`]
+/// to parse it as [`MateParam::Moves`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MateParam {
     /// Find a mate in this many millisecs
     Timeout(Duration),
+    /// Find a mate in this many moves
+    Moves(u16),
     /// Search indefinitely long until finding a forced mate
     Infinite,
 }
 
+/// The kind of search requested by a "go" command, see [`EngineParams::interpret`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GoMode {
+    /// Search in ponder mode ("go ponder ...") until "ponderhit" or "stop".
+    Ponder,
+
+    /// Search for a mate ("go mate ...").
+    Mate(MateParam),
+
+    /// Search until "stop" ("go infinite", or a bare "go").
+    Infinite,
+
+    /// Search this many plies ("go depth ...").
+    FixedDepth(u16),
+
+    /// Search this many nodes ("go nodes ...").
+    FixedNodes(u32),
+
+    /// Search exactly this long ("go movetime ...").
+    MoveTime(Duration),
+
+    /// Search a game move under clock control ("go btime ... wtime ...").
+    Clock {
+        btime: Option<Duration>,
+        wtime: Option<Duration>,
+        binc: Option<Duration>,
+        winc: Option<Duration>,
+        byoyomi: Option<Duration>,
+        movestogo: Option<u16>,
+    },
+}
+
+/// The order in which [`EngineParams::format_go`] writes the "go" subcommands.
+///
+/// Well-behaved engines accept the subcommands in any order, but some parse them
+/// positionally and expect the order used by the GUI they were tested with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GoFormat {
+    /// The order used by `Display`:
+    /// `ponder btime wtime binc winc byoyomi movestogo depth nodes mate movetime infinite searchmoves`.
+    #[default]
+    Standard,
+
+    /// The order in which the UCI spec lists the subcommands, with `searchmoves` first
+    /// and black/white swapped into UCI's white-first order:
+    /// `searchmoves ponder wtime btime winc binc byoyomi movestogo depth nodes mate movetime infinite`.
+    Uci,
+}
+
+#[derive(Clone, Copy)]
+enum GoField {
+    Ponder,
+    Btime,
+    Wtime,
+    Binc,
+    Winc,
+    Byoyomi,
+    Movestogo,
+    Depth,
+    Nodes,
+    Mate,
+    Movetime,
+    Infinite,
+    Searchmoves,
+}
+
+impl GoFormat {
+    fn fields(self) -> &'static [GoField] {
+        use GoField::*;
+        match self {
+            GoFormat::Standard => &[
+                Ponder,
+                Btime,
+                Wtime,
+                Binc,
+                Winc,
+                Byoyomi,
+                Movestogo,
+                Depth,
+                Nodes,
+                Mate,
+                Movetime,
+                Infinite,
+                Searchmoves,
+            ],
+            GoFormat::Uci => &[
+                Searchmoves,
+                Ponder,
+                Wtime,
+                Btime,
+                Winc,
+                Binc,
+                Byoyomi,
+                Movestogo,
+                Depth,
+                Nodes,
+                Mate,
+                Movetime,
+                Infinite,
+            ],
+        }
+    }
+}
+
 impl EngineParams {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Interpret the params as one kind of search.
+    ///
+    /// A "go" command may set several params at once. The mode is chosen by the
+    /// first match in this order: `ponder`, `mate`, `infinite`, `depth`, `nodes`,
+    /// `movetime`, and finally any of the clock params (`btime`, `wtime`, `binc`,
+    /// `winc`, `byoyomi`). A "go" without any of these is interpreted as
+    /// [`GoMode::Infinite`]. Note that `ponder` comes first since a pondering
+    /// engine also receives the clock params, to be used after "ponderhit".
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let GuiMessage::Go(params) = GuiMessage::parse("go depth 8\n").unwrap() else {
+    ///     panic!();
+    /// };
+    /// assert_eq!(params.interpret(), GoMode::FixedDepth(8));
+    /// ```
+    pub fn interpret(&self) -> GoMode {
+        if self.ponder {
+            GoMode::Ponder
+        } else if let Some(mate) = self.mate {
+            GoMode::Mate(mate)
+        } else if self.infinite {
+            GoMode::Infinite
+        } else if let Some(depth) = self.depth {
+            GoMode::FixedDepth(depth)
+        } else if let Some(nodes) = self.nodes {
+            GoMode::FixedNodes(nodes)
+        } else if let Some(movetime) = self.movetime {
+            GoMode::MoveTime(movetime)
+        } else if self.btime.is_some()
+            || self.wtime.is_some()
+            || self.binc.is_some()
+            || self.winc.is_some()
+            || self.byoyomi.is_some()
+        {
+            GoMode::Clock {
+                btime: self.btime,
+                wtime: self.wtime,
+                binc: self.binc,
+                winc: self.winc,
+                byoyomi: self.byoyomi,
+                movestogo: self.movestogo,
+            }
+        } else {
+            GoMode::Infinite
+        }
+    }
+
+    /// Are no clock fields (`btime`, `wtime`, `binc`, `winc`, `byoyomi`, `movestogo`) set?
+    ///
+    /// Search limits like `depth`, `nodes` or `movetime` are not clock fields, so
+    /// `go depth 20` is analysis-only.
+    pub fn analysis_only(&self) -> bool {
+        self.btime.is_none()
+            && self.wtime.is_none()
+            && self.binc.is_none()
+            && self.winc.is_none()
+            && self.byoyomi.is_none()
+            && self.movestogo.is_none()
+    }
+
+    /// Has `side` run out of time?
+    ///
+    /// This is true if the params give `side` a main time of zero, and there is no
+    /// byoyomi and no increment for `side` to fall back on (absent or zero). Without
+    /// a main time for `side`, as in `go infinite`, there is no clock to run out.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// use haitaka_types::Color;
+    /// let params = EngineParams::new().btime(0).wtime(60000).byoyomi(0);
+    /// assert!(params.is_time_forfeit(Color::Black));
+    /// assert!(!params.is_time_forfeit(Color::White));
+    /// ```
+    pub fn is_time_forfeit(&self, side: Color) -> bool {
+        let (time, inc) = match side {
+            Color::Black => (self.btime, self.binc),
+            Color::White => (self.wtime, self.winc),
+        };
+        let none_left = |t: Option<Duration>| t.is_none_or(|t| t.is_zero());
+        time.is_some_and(|t| t.is_zero()) && none_left(self.byoyomi) && none_left(inc)
+    }
+
+    /// Clear all clock fields (see [`EngineParams::analysis_only`]), keeping the search
+    /// limits. This turns a game-clock template into analysis params.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let params = EngineParams::new().btime(1000).wtime(1000).byoyomi(500).depth(20);
+    /// assert_eq!(params.strip_time().to_string(), " depth 20");
+    /// ```
+    #[must_use]
+    pub fn strip_time(mut self) -> Self {
+        self.btime = None;
+        self.wtime = None;
+        self.binc = None;
+        self.winc = None;
+        self.byoyomi = None;
+        self.movestogo = None;
+        self
+    }
+
+    // Millisecond getters. These return exactly the integers that `Display` emits,
+    // saving clients from casting `Duration::as_millis` (a `u128`) themselves.
+
+    /// Black time left in whole milliseconds.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let GuiMessage::Go(params) = GuiMessage::parse("go btime 60000 wtime 50000\n").unwrap() else {
+    ///     panic!();
+    /// };
+    /// assert_eq!(params.btime_ms(), Some(60000));
+    /// assert_eq!(params.byoyomi_ms(), None);
+    /// ```
+    pub fn btime_ms(&self) -> Option<u64> {
+        self.btime.map(whole_millis)
+    }
+
+    /// White time left in whole milliseconds.
+    pub fn wtime_ms(&self) -> Option<u64> {
+        self.wtime.map(whole_millis)
+    }
+
+    /// Black time increment in whole milliseconds.
+    pub fn binc_ms(&self) -> Option<u64> {
+        self.binc.map(whole_millis)
+    }
+
+    /// White time increment in whole milliseconds.
+    pub fn winc_ms(&self) -> Option<u64> {
+        self.winc.map(whole_millis)
+    }
+
+    /// Byoyomi in whole milliseconds.
+    pub fn byoyomi_ms(&self) -> Option<u64> {
+        self.byoyomi.map(whole_millis)
+    }
+
+    /// Fixed search time in whole milliseconds.
+    pub fn movetime_ms(&self) -> Option<u64> {
+        self.movetime.map(whole_millis)
+    }
+
+    /// The argument of "go mate". This is named `mate_param` since [`EngineParams::mate`]
+    /// is the setter.
+    pub fn mate_param(&self) -> Option<&MateParam> {
+        self.mate.as_ref()
+    }
+
+    /// Restrict the search to these moves. An empty list clears the restriction,
+    /// since "searchmoves" without moves can not be sent.
     #[must_use]
-    pub fn searchmoves(mut self, moves: Vec<Move>) -> Self {
-        self.searchmoves = Some(moves);
+    pub fn searchmoves(mut self, moves: impl Into<MoveList>) -> Self {
+        let moves = moves.into();
+        self.searchmoves = if moves.is_empty() { None } else { Some(moves) };
         self
     }
 
+    /// Set the searchmoves from USI move strings.
+    ///
+    /// Fails with the list of tokens that are not valid moves (see [`InvalidMoves`]).
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let params = EngineParams::new().searchmoves_from_strs(&["7g7f", "2g2f"]).unwrap();
+    /// assert_eq!(params.to_string(), " searchmoves 7g7f 2g2f");
+    ///
+    /// let err = EngineParams::new().searchmoves_from_strs(&["7g7f", "oops"]).unwrap_err();
+    /// assert_eq!(err.0, vec!["oops".to_string()]);
+    /// assert_eq!(err.to_string(), "invalid moves: oops");
+    /// ```
+    pub fn searchmoves_from_strs(self, moves: &[&str]) -> Result<Self, InvalidMoves> {
+        let mut parsed = Vec::with_capacity(moves.len());
+        let mut invalid = Vec::new();
+        for token in moves {
+            match token.parse::<Move>() {
+                Ok(mv) => parsed.push(mv),
+                Err(_) => invalid.push(token.to_string()),
+            }
+        }
+        if invalid.is_empty() {
+            Ok(self.searchmoves(parsed))
+        } else {
+            Err(InvalidMoves(invalid))
+        }
+    }
+
     #[must_use]
     pub fn ponder(mut self) -> Self {
         self.ponder = true;
@@ -266,6 +584,18 @@ impl EngineParams {
         self
     }
 
+    /// Search for a mate for this many milliseconds ("go mate 5000").
+    #[must_use]
+    pub fn mate_ms(self, ms: u64) -> Self {
+        self.mate(MateParam::Timeout(Duration::from_millis(ms)))
+    }
+
+    /// Search for a mate until one is found or "stop" is sent ("go mate infinite").
+    #[must_use]
+    pub fn mate_infinite(self) -> Self {
+        self.mate(MateParam::Infinite)
+    }
+
     #[must_use]
     pub fn movetime<T: IntoDuration>(mut self, t: T) -> Self {
         self.movetime = Some(t.into_duration());
@@ -277,10 +607,386 @@ impl EngineParams {
         self.infinite = true;
         self
     }
+
+    /// Cap every time field at `max`.
+    ///
+    /// This caps btime, wtime, binc, winc, byoyomi, movetime and the timeout of
+    /// `mate`. Fields that are not set are left unset, and zero times are left as
+    /// they are. This is a safety valve against a misbehaving GUI, not a validation:
+    /// the result is still a "go" the GUI could have sent.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// use std::time::Duration;
+    /// let params = EngineParams::new().btime(7_200_000).byoyomi(10_000);
+    /// let params = params.clamp_times(Duration::from_secs(60));
+    /// assert_eq!(params.btime_ms(), Some(60_000));
+    /// assert_eq!(params.byoyomi_ms(), Some(10_000));
+    /// ```
+    #[must_use]
+    pub fn clamp_times(mut self, max: Duration) -> Self {
+        let clamp = |t: &mut Option<Duration>| {
+            if let Some(t) = t {
+                *t = (*t).min(max);
+            }
+        };
+        clamp(&mut self.btime);
+        clamp(&mut self.wtime);
+        clamp(&mut self.binc);
+        clamp(&mut self.winc);
+        clamp(&mut self.byoyomi);
+        clamp(&mut self.movetime);
+        if let Some(MateParam::Timeout(t)) = &mut self.mate {
+            *t = (*t).min(max);
+        }
+        self
+    }
+
+    /// Format the params in the given subcommand order.
+    ///
+    /// The result is either empty or starts with a space, so that it can be appended
+    /// to "go". `Display` uses [`GoFormat::Standard`].
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let params = EngineParams::new().btime(1000).wtime(2000);
+    /// assert_eq!(params.format_go(GoFormat::Standard), " btime 1000 wtime 2000");
+    /// assert_eq!(params.format_go(GoFormat::Uci), " wtime 2000 btime 1000");
+    /// ```
+    pub fn format_go(&self, format: GoFormat) -> String {
+        let mut params = String::new();
+        for field in format.fields() {
+            self.write_field(*field, &mut params);
+        }
+        params
+    }
+
+    fn write_field(&self, field: GoField, params: &mut String) {
+        match field {
+            GoField::Ponder => {
+                if self.ponder {
+                    *params += " ponder";
+                }
+            }
+            GoField::Btime => {
+                if let Some(btime) = self.btime {
+                    *params += &format!(" btime {}", whole_millis(btime));
+                }
+            }
+            GoField::Wtime => {
+                if let Some(wtime) = self.wtime {
+                    *params += &format!(" wtime {}", whole_millis(wtime));
+                }
+            }
+            GoField::Binc => {
+                if let Some(binc) = self.binc {
+                    *params += &format!(" binc {}", whole_millis(binc));
+                }
+            }
+            GoField::Winc => {
+                if let Some(winc) = self.winc {
+                    *params += &format!(" winc {}", whole_millis(winc));
+                }
+            }
+            GoField::Byoyomi => {
+                if let Some(byoyomi) = self.byoyomi {
+                    *params += &format!(" byoyomi {}", whole_millis(byoyomi));
+                }
+            }
+            GoField::Movestogo => {
+                if let Some(movestogo) = self.movestogo {
+                    *params += &format!(" movestogo {}", movestogo);
+                }
+            }
+            GoField::Depth => {
+                if let Some(depth) = self.depth {
+                    *params += &format!(" depth {}", depth);
+                }
+            }
+            GoField::Nodes => {
+                if let Some(nodes) = self.nodes {
+                    *params += &format!(" nodes {}", nodes);
+                }
+            }
+            GoField::Mate => match self.mate {
+                Some(MateParam::Timeout(duration)) => {
+                    *params += &format!(" mate {}", whole_millis(duration));
+                }
+                Some(MateParam::Moves(n)) => {
+                    *params += &format!(" mate {}", n);
+                }
+                Some(MateParam::Infinite) => {
+                    *params += " mate infinite";
+                }
+                None => {}
+            },
+            GoField::Movetime => {
+                if let Some(movetime) = self.movetime {
+                    *params += &format!(" movetime {}", whole_millis(movetime));
+                }
+            }
+            GoField::Infinite => {
+                if self.infinite {
+                    *params += " infinite";
+                }
+            }
+            GoField::Searchmoves => {
+                if let Some(ref moves) = self.searchmoves {
+                    *params += &format!(" searchmoves {}", moves);
+                }
+            }
+        }
+    }
+
+    /// A compact human-readable summary of the params, for display in a UI.
+    ///
+    /// This is not the wire format (see `Display`). Main times are formatted as
+    /// minutes and seconds, increments and byoyomi as seconds. A bare "go" gives
+    /// an empty string.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let params = EngineParams::new().btime(300_000).binc(10_000).byoyomi(30_000).depth(20);
+    /// assert_eq!(params.describe(), "Black 5:00 + 10s, byoyomi 30s, depth ≤ 20");
+    /// ```
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.ponder {
+            parts.push("ponder".to_string());
+        }
+        for (side, time, inc) in [
+            ("Black", self.btime, self.binc),
+            ("White", self.wtime, self.winc),
+        ] {
+            match (time, inc) {
+                (Some(time), Some(inc)) if !inc.is_zero() => parts.push(format!(
+                    "{} {} + {}",
+                    side,
+                    minutes_seconds(time),
+                    seconds(inc)
+                )),
+                (Some(time), _) => parts.push(format!("{} {}", side, minutes_seconds(time))),
+                (None, Some(inc)) => parts.push(format!("{} + {}", side, seconds(inc))),
+                (None, None) => {}
+            }
+        }
+        if let Some(byoyomi) = self.byoyomi {
+            parts.push(format!("byoyomi {}", seconds(byoyomi)));
+        }
+        if let Some(movestogo) = self.movestogo {
+            parts.push(format!("{} moves to go", movestogo));
+        }
+        if let Some(depth) = self.depth {
+            parts.push(format!("depth ≤ {}", depth));
+        }
+        if let Some(nodes) = self.nodes {
+            parts.push(format!("nodes ≤ {}", nodes));
+        }
+        match self.mate {
+            Some(MateParam::Timeout(duration)) => {
+                parts.push(format!("mate search {}", seconds(duration)))
+            }
+            Some(MateParam::Moves(n)) => parts.push(format!("mate in {}", n)),
+            Some(MateParam::Infinite) => parts.push("mate search".to_string()),
+            None => {}
+        }
+        if let Some(movetime) = self.movetime {
+            parts.push(format!("movetime {}", seconds(movetime)));
+        }
+        if self.infinite {
+            parts.push("infinite".to_string());
+        }
+        if let Some(ref moves) = self.searchmoves {
+            parts.push(format!("only {}", moves));
+        }
+        parts.join(", ")
+    }
+
+    // Non-consuming variants of the setters, which return a modified copy. These are
+    // convenient to derive a new "go" from a template, such as an already parsed one.
+
+    #[must_use]
+    pub fn with_searchmoves(&self, moves: impl Into<MoveList>) -> Self {
+        self.clone().searchmoves(moves)
+    }
+
+    #[must_use]
+    pub fn with_btime<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().btime(t)
+    }
+
+    #[must_use]
+    pub fn with_wtime<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().wtime(t)
+    }
+
+    #[must_use]
+    pub fn with_binc<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().binc(t)
+    }
+
+    #[must_use]
+    pub fn with_winc<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().winc(t)
+    }
+
+    #[must_use]
+    pub fn with_byoyomi<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().byoyomi(t)
+    }
+
+    #[must_use]
+    pub fn with_movestogo(&self, n: u16) -> Self {
+        self.clone().movestogo(n)
+    }
+
+    #[must_use]
+    pub fn with_depth(&self, n: u16) -> Self {
+        self.clone().depth(n)
+    }
+
+    #[must_use]
+    pub fn with_nodes(&self, n: u32) -> Self {
+        self.clone().nodes(n)
+    }
+
+    #[must_use]
+    pub fn with_mate(&self, t: MateParam) -> Self {
+        self.clone().mate(t)
+    }
+
+    #[must_use]
+    pub fn with_movetime<T: IntoDuration>(&self, t: T) -> Self {
+        self.clone().movetime(t)
+    }
+}
+
+impl GuiMessage {
+    /// The `position startpos` message.
+    pub const fn startpos() -> Self {
+        Self::Position {
+            sfen: None,
+            moves: None,
+        }
+    }
+
+    /// The `position startpos moves ...` message. An empty move list gives
+    /// `position startpos`.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let moves: MoveList = "7g7f 3c3d".parse().unwrap();
+    /// let msg = GuiMessage::startpos_with_moves(moves);
+    /// assert_eq!(msg.to_string(), "position startpos moves 7g7f 3c3d");
+    /// ```
+    pub fn startpos_with_moves(moves: impl Into<MoveList>) -> Self {
+        let moves = moves.into();
+        Self::Position {
+            sfen: None,
+            moves: if moves.is_empty() { None } else { Some(moves) },
+        }
+    }
+
+    /// Serialize the message as a newline-terminated protocol line.
+    pub fn to_usi_line(&self) -> String {
+        self.to_usi_line_with(LineEnding::Lf)
+    }
+
+    /// Serialize the message as a protocol line with the given line ending.
+    pub fn to_usi_line_with(&self, line_ending: LineEnding) -> String {
+        format!("{}{}", self, line_ending)
+    }
+
+    /// Is this a `usi` message?
+    pub fn is_usi(&self) -> bool {
+        matches!(self, Self::Usi)
+    }
+
+    /// Is this a `debug` message?
+    pub fn is_debug(&self) -> bool {
+        matches!(self, Self::Debug(_))
+    }
+
+    /// Is this an `isready` message?
+    pub fn is_isready(&self) -> bool {
+        matches!(self, Self::IsReady)
+    }
+
+    /// Is this a `setoption` message?
+    pub fn is_setoption(&self) -> bool {
+        matches!(self, Self::SetOption { .. })
+    }
+
+    /// Is this a `register` message?
+    pub fn is_register(&self) -> bool {
+        matches!(self, Self::Register { .. })
+    }
+
+    /// Is this a `usinewgame` message?
+    pub fn is_usinewgame(&self) -> bool {
+        matches!(self, Self::UsiNewGame)
+    }
+
+    /// Is this a `position` message?
+    pub fn is_position(&self) -> bool {
+        matches!(self, Self::Position { .. })
+    }
+
+    /// Is this a `go` message?
+    pub fn is_go(&self) -> bool {
+        matches!(self, Self::Go(_))
+    }
+
+    /// Is this a `stop` message?
+    pub fn is_stop(&self) -> bool {
+        matches!(self, Self::Stop)
+    }
+
+    /// Is this a `ponderhit` message?
+    pub fn is_ponderhit(&self) -> bool {
+        matches!(self, Self::PonderHit)
+    }
+
+    /// Is this a `gameover` message?
+    pub fn is_gameover(&self) -> bool {
+        matches!(self, Self::GameOver(_))
+    }
+
+    /// Is this a `quit` message?
+    pub fn is_quit(&self) -> bool {
+        matches!(self, Self::Quit)
+    }
+
+    /// Is this a message that does not conform to the protocol?
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+
+    /// The command keyword of the message (`"go"`, `"position"`, ...), or `"unknown"`.
+    ///
+    /// This is meant for keying metrics or logs by message type.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Usi => "usi",
+            Self::Debug(_) => "debug",
+            Self::IsReady => "isready",
+            Self::SetOption { .. } => "setoption",
+            Self::Register { .. } => "register",
+            Self::UsiNewGame => "usinewgame",
+            Self::Position { .. } => "position",
+            Self::Go(_) => "go",
+            Self::Stop => "stop",
+            Self::PonderHit => "ponderhit",
+            Self::GameOver(_) => "gameover",
+            Self::Quit => "quit",
+            Self::Unknown(_) => "unknown",
+        }
+    }
 }
 
 // Note that the Display for GuiMessage does not add a terminating newline character.
-// When actually sending protocol messages a writer should add the '\n'.
+// When actually sending protocol messages a writer should add the '\n'
+// (or use `to_usi_line`).
 
 impl fmt::Display for GuiMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -292,6 +998,7 @@ impl fmt::Display for GuiMessage {
             },
             GuiMessage::IsReady => write!(f, "isready"),
             GuiMessage::SetOption { name, value } => match value {
+                Some(value) if value.is_empty() => write!(f, "setoption name {name} value <empty>"),
                 Some(value) => write!(f, "setoption name {name} value {value}"),
                 _ => write!(f, "setoption name {name}"),
             },
@@ -305,11 +1012,11 @@ impl fmt::Display for GuiMessage {
             GuiMessage::Position { sfen, moves } => match (sfen, moves) {
                 (None, None) => write!(f, "position startpos"),
                 (None, Some(moves)) => {
-                    write!(f, "position startpos moves {}", format_vec!(moves))
+                    write!(f, "position startpos moves {}", moves)
                 }
                 (Some(sfen), None) => write!(f, "position sfen {}", sfen),
                 (Some(sfen), Some(moves)) => {
-                    write!(f, "position sfen {} moves {}", sfen, format_vec!(moves))
+                    write!(f, "position sfen {} moves {}", sfen, moves)
                 }
             },
             GuiMessage::Go(params) => write!(f, "go{}", params), // params starts with space if non-empty
@@ -332,58 +1039,33 @@ impl fmt::Display for GameStatus {
     }
 }
 
-impl fmt::Display for EngineParams {
+/// The error returned by [`EngineParams::searchmoves_from_strs`], with the tokens
+/// which are not valid moves.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InvalidMoves(pub Vec<String>);
+
+impl fmt::Display for InvalidMoves {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut params = String::new();
+        write!(f, "invalid moves: {}", self.0.join(" "))
+    }
+}
 
-        if self.ponder {
-            params += " ponder";
-        }
-        if let Some(btime) = self.btime {
-            params += &format!(" btime {}", btime.as_millis());
-        }
-        if let Some(wtime) = self.wtime {
-            params += &format!(" wtime {}", wtime.as_millis());
-        }
-        if let Some(binc) = self.binc {
-            params += &format!(" binc {}", binc.as_millis());
-        }
-        if let Some(winc) = self.winc {
-            params += &format!(" winc {}", winc.as_millis());
-        }
-        if let Some(byoyomi) = self.byoyomi {
-            params += &format!(" byoyomi {}", byoyomi.as_millis());
-        }
-        if let Some(movestogo) = self.movestogo {
-            params += &format!(" movestogo {}", movestogo);
-        }
-        if let Some(depth) = self.depth {
-            params += &format!(" depth {}", depth);
-        }
-        if let Some(nodes) = self.nodes {
-            params += &format!(" nodes {}", nodes);
-        }
-        if let Some(ref mate) = self.mate {
-            match mate {
-                MateParam::Timeout(duration) => {
-                    params += &format!(" mate {}", duration.as_millis());
-                }
-                MateParam::Infinite => {
-                    params += " mate infinite";
-                }
-            }
-        }
-        if let Some(movetime) = self.movetime {
-            params += &format!(" movetime {}", movetime.as_millis());
-        }
-        if self.infinite {
-            params += " infinite";
-        }
-        if let Some(ref moves) = self.searchmoves {
-            params += &format!(" searchmoves {}", format_vec!(moves));
-        }
+impl std::error::Error for InvalidMoves {}
+
+/// Format a duration as minutes and seconds ("5:00"), for [`EngineParams::describe`].
+fn minutes_seconds(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
 
+/// Format a duration as seconds ("10s", "0.5s"), for [`EngineParams::describe`].
+fn seconds(d: Duration) -> String {
+    format!("{}s", d.as_secs_f64())
+}
+
+impl fmt::Display for EngineParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // the output string will either be empty or start with a space
-        write!(f, "{}", params)
+        write!(f, "{}", self.format_go(GoFormat::Standard))
     }
 }
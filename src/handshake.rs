@@ -0,0 +1,102 @@
+//! This module implements timing of the protocol handshakes.
+//!
+//! GUIs typically time out if an engine takes too long to answer `usi` with `usiok`,
+//! or `isready` with `readyok`. The [`HandshakeTimer`] records when these messages
+//! pass through (for instance in a proxy or a GUI) and reports the elapsed times.
+use crate::engine::EngineMessage;
+use crate::gui::GuiMessage;
+use std::time::{Duration, Instant};
+
+/// Measures the `usi`/`usiok` and `isready`/`readyok` round-trip times.
+///
+/// Feed the GUI messages to [`HandshakeTimer::observe_gui`] and the engine messages to
+/// [`HandshakeTimer::observe`]. The time source can be replaced by a custom clock,
+/// see [`HandshakeTimer::with_clock`].
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut timer = HandshakeTimer::new();
+/// timer.observe_gui(&GuiMessage::IsReady);
+/// assert_eq!(timer.isready_to_readyok(), None);
+/// timer.observe(&EngineMessage::ReadyOk);
+/// assert!(timer.isready_to_readyok().is_some());
+/// ```
+pub struct HandshakeTimer<C = fn() -> Instant> {
+    clock: C,
+    usi: Option<Instant>,
+    isready: Option<Instant>,
+    usi_to_usiok: Option<Duration>,
+    isready_to_readyok: Option<Duration>,
+}
+
+impl HandshakeTimer {
+    /// Create a timer using the system clock.
+    pub fn new() -> Self {
+        Self::with_clock(Instant::now)
+    }
+}
+
+impl Default for HandshakeTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Fn() -> Instant> HandshakeTimer<C> {
+    /// Create a timer using the given clock.
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            usi: None,
+            isready: None,
+            usi_to_usiok: None,
+            isready_to_readyok: None,
+        }
+    }
+
+    /// Observe a message sent by the GUI. This starts the timing on `usi` and `isready`.
+    pub fn observe_gui(&mut self, msg: &GuiMessage) {
+        match msg {
+            GuiMessage::Usi => {
+                self.usi = Some((self.clock)());
+                self.usi_to_usiok = None;
+            }
+            GuiMessage::IsReady => {
+                self.isready = Some((self.clock)());
+                self.isready_to_readyok = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Observe a message sent by the engine. This stops the timing on `usiok` and `readyok`.
+    ///
+    /// A `usiok` or `readyok` without a preceding `usi` or `isready` is ignored.
+    pub fn observe(&mut self, msg: &EngineMessage) {
+        match msg {
+            EngineMessage::UsiOk => {
+                if let Some(start) = self.usi.take() {
+                    self.usi_to_usiok = Some((self.clock)().saturating_duration_since(start));
+                }
+            }
+            EngineMessage::ReadyOk => {
+                if let Some(start) = self.isready.take() {
+                    self.isready_to_readyok = Some((self.clock)().saturating_duration_since(start));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// The time between the last `usi` and its `usiok`, if completed.
+    pub fn usi_to_usiok(&self) -> Option<Duration> {
+        self.usi_to_usiok
+    }
+
+    /// The time between the last `isready` and its `readyok`, if completed.
+    pub fn isready_to_readyok(&self) -> Option<Duration> {
+        self.isready_to_readyok
+    }
+}
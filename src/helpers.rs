@@ -1,9 +1,12 @@
 //! Some utilities.
+use haitaka_types::Move;
+use std::fmt::{Display, Write};
 use std::time::Duration;
 
 /// Convert a vector of items into a string.
 ///
-/// The items should support `.to_string()`.
+/// The items should support `.to_string()`. This macro requires an identifier,
+/// see [`join_display`] and [`join_moves`] for plain functions taking a slice.
 #[macro_export]
 macro_rules! format_vec {
     ($items:ident) => {
@@ -18,14 +21,77 @@ macro_rules! format_vec {
     };
 }
 
+/// Join the `Display` representations of the items with a separator.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::join_display;
+/// assert_eq!(join_display(&[1, 2, 3], ", "), "1, 2, 3");
+/// ```
+pub fn join_display<T: Display>(items: &[T], sep: &str) -> String {
+    let mut out = String::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out += sep;
+        }
+        let _ = write!(out, "{}", item);
+    }
+    out
+}
+
+/// Join moves with single spaces, as in the move lists of the protocol.
+pub fn join_moves(moves: &[Move]) -> String {
+    join_display(moves, " ")
+}
+
+/// Format moves exactly as this crate emits them in a move list (`pv`, `position ... moves`,
+/// `go searchmoves`). This is the inverse of parsing a move list.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::moves_to_usi;
+/// use haitaka_types::Move;
+/// let moves: Vec<Move> = "7g7f 3c3d P*5e 2b3a+"
+///     .split_whitespace()
+///     .map(|mv| mv.parse().unwrap())
+///     .collect();
+/// assert_eq!(moves_to_usi(&moves), "7g7f 3c3d P*5e 2b3a+");
+/// ```
+pub fn moves_to_usi(moves: &[Move]) -> String {
+    join_moves(moves)
+}
+
+/// Return the number of whole milliseconds in a Duration, rounded to the nearest
+/// millisecond (halfway values are rounded up).
+///
+/// All times in the USI protocol are integer milliseconds. This function is used
+/// when serializing Durations, so that sub-millisecond precision is rounded rather
+/// than silently truncated. Durations that don't fit in a `u64` saturate.
+pub fn whole_millis(d: Duration) -> u64 {
+    let ms = (d.as_nanos() + 500_000) / 1_000_000;
+    u64::try_from(ms).unwrap_or(u64::MAX)
+}
+
+/// Round a Duration to the nearest whole millisecond.
+///
+/// The result is guaranteed to round-trip through the USI wire format.
+pub fn round_to_millis(d: Duration) -> Duration {
+    Duration::from_millis(whole_millis(d))
+}
+
 /// A little custom trait to make it more convenient to work with Durations.
+///
+/// Since the USI wire format only supports whole milliseconds, Durations are
+/// normalized with [`round_to_millis`].
 pub trait IntoDuration {
     fn into_duration(self) -> Duration;
 }
 
 impl IntoDuration for Duration {
     fn into_duration(self) -> Duration {
-        self
+        round_to_millis(self)
     }
 }
 
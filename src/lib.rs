@@ -1,14 +1,42 @@
 #![doc = include_str!("../README.md")]
 
+pub mod clock;
 pub mod engine;
+pub mod eval;
 pub mod gui;
+pub mod handshake;
 pub mod helpers;
+pub mod moves;
+pub mod multipv;
+pub mod options;
 pub mod parser;
+pub mod ponder;
+pub mod reader;
+pub mod record;
+pub mod sfen;
+pub mod usi;
+pub mod widget;
+pub mod writer;
 
+pub use clock::*;
 pub use engine::*;
+pub use eval::*;
 pub use gui::*;
+pub use handshake::*;
 pub use helpers::*;
+pub use moves::*;
+pub use multipv::*;
+pub use options::*;
 pub use parser::*;
+pub use ponder::*;
+pub use reader::*;
+pub use record::*;
+pub use sfen::*;
+pub use usi::*;
+pub use widget::*;
+pub use writer::*;
+
+pub use haitaka_types::Square;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,160 @@
+//! This module contains [`MoveList`], the move list used in the protocol messages
+//! (`position ... moves`, `go searchmoves`, `info pv`, `checkmate`, ...).
+use crate::helpers::join_moves;
+use haitaka_types::{Move, MoveParseError, Square};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// A list of moves.
+///
+/// A `MoveList` displays as the space-separated USI move strings, and parses from
+/// the same format. Move lists are ordered lexicographically, see [`cmp_moves`].
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let moves: MoveList = "7g7f 3c3d P*5e".parse().unwrap();
+/// assert_eq!(moves.len(), 3);
+/// assert_eq!(moves.to_string(), "7g7f 3c3d P*5e");
+/// assert!("7g7f oops".parse::<MoveList>().is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MoveList(Vec<Move>);
+
+/// A total order on moves, since [`Move`] does not implement `Ord`.
+///
+/// Drops come before board moves. Drops are ordered by piece, then target square.
+/// Board moves are ordered by source square, then target square, then promotion.
+pub fn cmp_moves(a: &Move, b: &Move) -> Ordering {
+    match (a, b) {
+        (Move::Drop { piece: p1, to: t1 }, Move::Drop { piece: p2, to: t2 }) => {
+            (p1, t1).cmp(&(p2, t2))
+        }
+        (Move::Drop { .. }, Move::BoardMove { .. }) => Ordering::Less,
+        (Move::BoardMove { .. }, Move::Drop { .. }) => Ordering::Greater,
+        (
+            Move::BoardMove {
+                from: f1,
+                to: t1,
+                promotion: p1,
+            },
+            Move::BoardMove {
+                from: f2,
+                to: t2,
+                promotion: p2,
+            },
+        ) => (f1, t1, p1).cmp(&(f2, t2, p2)),
+    }
+}
+
+/// The source and target square of a move. The source is `None` for a drop.
+///
+/// ```
+/// use haitaka_usi::*;
+/// let (from, to) = move_squares(&"7g7f".parse().unwrap());
+/// assert_eq!((from, to), (Some(Square::G7), Square::F7));
+/// assert_eq!(move_squares(&"P*5e".parse().unwrap()), (None, Square::E5));
+/// ```
+pub fn move_squares(mv: &Move) -> (Option<Square>, Square) {
+    match *mv {
+        Move::Drop { to, .. } => (None, to),
+        Move::BoardMove { from, to, .. } => (Some(from), to),
+    }
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn as_slice(&self) -> &[Move] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<Move> {
+        self.0
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        self.0.push(mv);
+    }
+}
+
+impl Ord for MoveList {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| cmp_moves(a, b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+impl PartialOrd for MoveList {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.0
+    }
+}
+
+impl From<Vec<Move>> for MoveList {
+    fn from(moves: Vec<Move>) -> Self {
+        Self(moves)
+    }
+}
+
+impl From<MoveList> for Vec<Move> {
+    fn from(moves: MoveList) -> Self {
+        moves.0
+    }
+}
+
+impl FromIterator<Move> for MoveList {
+    fn from_iter<I: IntoIterator<Item = Move>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = std::vec::IntoIter<Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl FromStr for MoveList {
+    type Err = MoveParseError;
+
+    /// Parse white-space-separated USI moves. An empty string is an empty list.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace().map(Move::from_str).collect()
+    }
+}
+
+impl fmt::Display for MoveList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", join_moves(&self.0))
+    }
+}
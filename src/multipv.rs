@@ -0,0 +1,110 @@
+//! This module implements [`MultiPvTable`], which collects the `info ... multipv N pv ...`
+//! lines of a search into a ranked list of variations.
+use crate::engine::{EngineMessage, InfoParam};
+use crate::moves::MoveList;
+use std::collections::BTreeMap;
+
+/// A principal variation reported in an `info` message.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PvLine {
+    /// The search depth, if reported.
+    pub depth: Option<u16>,
+
+    /// The score, if reported. This is either an [`InfoParam::ScoreCp`] or an
+    /// [`InfoParam::ScoreMate`].
+    pub score: Option<InfoParam>,
+
+    /// The variation.
+    pub pv: MoveList,
+}
+
+impl PvLine {
+    /// Extract the variation from the params of an `info` message.
+    ///
+    /// Returns `None` if the params contain no `pv`.
+    pub fn from_params(params: &[InfoParam]) -> Option<Self> {
+        let mut line = PvLine::default();
+        let mut has_pv = false;
+        for param in params {
+            match param {
+                InfoParam::Depth(d) => line.depth = Some(*d),
+                InfoParam::ScoreCp(..) | InfoParam::ScoreMate(..) => {
+                    line.score = Some(param.clone())
+                }
+                InfoParam::Pv(mvs) => {
+                    line.pv = mvs.clone();
+                    has_pv = true;
+                }
+                _ => {}
+            }
+        }
+        has_pv.then_some(line)
+    }
+}
+
+/// The latest variation for each multipv index of a search.
+///
+/// An `info` line without `multipv` counts as `multipv 1`. Lines without a `pv` are
+/// ignored. A `bestmove` ends the search and clears the table.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut table = MultiPvTable::new();
+/// for line in EngineMessageStream::new("info multipv 2 pv 2g2f\ninfo multipv 1 pv 7g7f\n") {
+///     table.update(&line);
+/// }
+/// let ranked = table.ranked();
+/// assert_eq!(ranked[0].0, 1);
+/// assert_eq!(ranked[0].1.pv.to_string(), "7g7f");
+/// table.update(&EngineMessage::parse("bestmove 7g7f\n").unwrap());
+/// assert!(table.is_empty());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MultiPvTable {
+    lines: BTreeMap<u16, PvLine>,
+}
+
+impl MultiPvTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold an engine message into the table.
+    pub fn update(&mut self, msg: &EngineMessage) {
+        match msg {
+            EngineMessage::Info(params) => {
+                if let Some((index, _)) = msg.pv_with_index()
+                    && let Some(line) = PvLine::from_params(params)
+                {
+                    self.lines.insert(index, line);
+                }
+            }
+            EngineMessage::BestMove(_) => self.clear(),
+            _ => {}
+        }
+    }
+
+    /// The variations with their multipv index, sorted by index.
+    pub fn ranked(&self) -> Vec<(u16, &PvLine)> {
+        self.lines.iter().map(|(n, line)| (*n, line)).collect()
+    }
+
+    /// The variation with this multipv index.
+    pub fn get(&self, index: u16) -> Option<&PvLine> {
+        self.lines.get(&index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
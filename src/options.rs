@@ -0,0 +1,338 @@
+//! Parser options.
+//!
+//! The main struct is [`ParseOptions`] which collects all the tunables of the parser.
+//! The default options implement the strict protocol. Lenient options are implemented
+//! as preprocessing steps which normalize the input line by line before it is handed
+//! to the grammar, so the grammar itself always stays strict.
+use crate::gui::GameStatus;
+use std::borrow::Cow;
+
+/// The default maximum number of moves in a parsed move list.
+pub const DEFAULT_MAX_MOVES: usize = 1024;
+
+/// The "no move" tokens that some engines borrowed from chess, as in `bestmove 0000`
+/// and `bestmove (none)`. See [`ParseOptions::no_move_tokens`].
+pub const NO_MOVE_TOKENS: &[Cow<'static, str>] = &[Cow::Borrowed("0000"), Cow::Borrowed("(none)")];
+
+/// Common English variants of the `gameover` results, see [`ParseOptions::gameover_synonyms`].
+///
+/// | synonym      | result                |
+/// |--------------|-----------------------|
+/// | `won`        | [`GameStatus::Win`]   |
+/// | `lost`       | [`GameStatus::Lose`]  |
+/// | `loss`       | [`GameStatus::Lose`]  |
+/// | `drawn`      | [`GameStatus::Draw`]  |
+/// | `sennichite` | [`GameStatus::Draw`]  |
+///
+/// Tools that report the winning side (`gameover sente`, `gameover gote`) need a table
+/// that depends on the side the engine plays, which has to be built by the caller.
+pub const GAMEOVER_SYNONYMS: &[(Cow<'static, str>, GameStatus)] = &[
+    (Cow::Borrowed("won"), GameStatus::Win),
+    (Cow::Borrowed("lost"), GameStatus::Lose),
+    (Cow::Borrowed("loss"), GameStatus::Lose),
+    (Cow::Borrowed("drawn"), GameStatus::Draw),
+    (Cow::Borrowed("sennichite"), GameStatus::Draw),
+];
+
+/// All command keywords of the protocol (GUI and Engine messages).
+const KEYWORDS: [&str; 21] = [
+    "usi",
+    "debug",
+    "isready",
+    "setoption",
+    "register",
+    "usinewgame",
+    "stop",
+    "ponderhit",
+    "quit",
+    "position",
+    "go",
+    "gameover",
+    "id",
+    "usiok",
+    "readyok",
+    "bestmove",
+    "checkmate",
+    "copyprotection",
+    "registration",
+    "option",
+    "info",
+];
+
+/// Options to tune the behavior of the parser.
+///
+/// The default options implement the strict protocol.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let options = ParseOptions { max_moves: 2, ..ParseOptions::default() };
+/// let msg = EngineMessage::parse_with("info pv 7g7f 3c3d 2g2f\n", &options).unwrap();
+/// assert_eq!(msg, EngineMessage::Info(vec![InfoParam::Pv("7g7f 3c3d".parse().unwrap())]));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    /// Maximum number of moves kept in the move lists of `go searchmoves` and of
+    /// `info pv`, `info refutation` and `info currline`. Moves past this limit are
+    /// dropped. This defends against buggy or malicious peers sending pathologically
+    /// long lines. The moves of a `position` command and of a `checkmate` solution are
+    /// never truncated, since that would silently change their meaning. Default:
+    /// [`DEFAULT_MAX_MOVES`].
+    pub max_moves: usize,
+
+    /// Match the command keyword at the start of a line case-insensitively, so that
+    /// `USI` or `IsReady` are accepted. Only the command keyword is affected. Sub-command
+    /// keywords, moves and SFEN strings remain case-sensitive. Default: `false`.
+    pub case_insensitive: bool,
+
+    /// Split lines on this in-line separator, so that one physical line can carry several
+    /// messages (`isready; usinewgame`). Each separator is treated as a line break. Note
+    /// that this also splits free-form text like `info string`, so only enable this for
+    /// peers that are known to use it. Default: `None`.
+    pub separator: Option<char>,
+
+    /// Parse the numeric argument of `go mate` as a number of moves ([`crate::MateParam::Moves`])
+    /// instead of a timeout in milliseconds ([`crate::MateParam::Timeout`]), as some tools
+    /// derived from UCI do. Default: `false`.
+    pub mate_in_moves: bool,
+
+    /// Accept `bestmove` followed by one of these tokens as [`crate::BestMoveParams::None`]
+    /// (the engine has no move). This is an extension borrowed from chess engines, not
+    /// part of the USI spec. A ponder move after the token (`bestmove 0000 ponder 7g7f`)
+    /// is accepted and dropped. Set this to `Cow::Borrowed(NO_MOVE_TOKENS)` to accept `0000`
+    /// and `(none)`, or to an owned `Vec` of tokens built at runtime. Default: empty.
+    pub no_move_tokens: Cow<'static, [Cow<'static, str>]>,
+
+    /// Accept `setoption` without the `name` keyword (`setoption USI_Hash value 128`),
+    /// a common but non-standard form. The first token after `setoption` is taken as
+    /// the option name. Default: `false`.
+    pub setoption_without_name: bool,
+
+    /// Accept `gameover` followed by one of these words as the paired result, as in
+    /// `gameover won`. The strict protocol only allows `win`, `lose` and `draw`. Set this
+    /// to `Cow::Borrowed(GAMEOVER_SYNONYMS)` for the common variants, or to an owned `Vec`
+    /// for a custom table built at runtime. Default: empty.
+    pub gameover_synonyms: Cow<'static, [(Cow<'static, str>, GameStatus)]>,
+
+    /// Accept `info` params written as `key=value`, as in `info depth=5 score=cp:120`.
+    /// This is a compatibility shim for a few experimental engines, not part of the
+    /// USI spec. Each `key=value` token is rewritten to `key value`, with any `:` in
+    /// the value also replaced by a space (`score=cp:120` becomes `score cp 120`).
+    /// The text after `string` is left as is. Default: `false`.
+    pub info_key_value: bool,
+
+    /// Accept a `%` after the number of `info hashfull` and `info cpuload`, as in
+    /// `info hashfull 45%`, which shows up in the logs of some tools. This is a
+    /// compatibility shim, the `%` is simply dropped: the number is not converted, and
+    /// is still reported as permille. Default: `false`.
+    pub info_percent_suffix: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_moves: DEFAULT_MAX_MOVES,
+            case_insensitive: false,
+            separator: None,
+            mate_in_moves: false,
+            no_move_tokens: Cow::Borrowed(&[]),
+            setoption_without_name: false,
+            gameover_synonyms: Cow::Borrowed(&[]),
+            info_key_value: false,
+            info_percent_suffix: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Apply all enabled preprocessing steps to the input.
+    ///
+    /// This returns the input unchanged (and without allocating) if no lenient
+    /// options are enabled.
+    pub(crate) fn preprocess<'a>(&self, input: &'a str) -> Cow<'a, str> {
+        let mut input = Cow::Borrowed(input);
+        if let Some(sep) = self.separator {
+            input = apply(input, |line| split_on(line, sep));
+        }
+        if self.case_insensitive {
+            input = apply(input, lowercase_keyword);
+        }
+        if self.setoption_without_name {
+            input = apply(input, insert_setoption_name);
+        }
+        if !self.gameover_synonyms.is_empty() {
+            let synonyms = &self.gameover_synonyms;
+            input = apply(input, |line| map_gameover_synonym(line, synonyms));
+        }
+        if self.info_key_value {
+            input = apply(input, split_info_key_value);
+        }
+        if self.info_percent_suffix {
+            input = apply(input, strip_info_percent);
+        }
+        input
+    }
+}
+
+/// Apply a line transformation to a (possibly already preprocessed) input.
+fn apply<'a, F>(input: Cow<'a, str>, f: F) -> Cow<'a, str>
+where
+    F: Fn(&str) -> Cow<'_, str>,
+{
+    let changed = match map_lines(&input, f) {
+        Cow::Owned(s) => Some(s),
+        Cow::Borrowed(_) => None,
+    };
+    match changed {
+        Some(s) => Cow::Owned(s),
+        None => input,
+    }
+}
+
+/// Apply a transformation to each line of the input, preserving the line endings.
+fn map_lines<'a, F>(input: &'a str, f: F) -> Cow<'a, str>
+where
+    F: Fn(&str) -> Cow<'_, str>,
+{
+    let mut out = String::with_capacity(input.len());
+    let mut changed = false;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = rest.find(['\n', '\r']).map_or(rest.len(), |i| i + 1);
+        let (line, tail) = rest.split_at(end);
+        let body = line.trim_end_matches(['\n', '\r']);
+        let mapped = f(body);
+        if let Cow::Owned(_) = mapped {
+            changed = true;
+        }
+        out.push_str(&mapped);
+        out.push_str(&line[body.len()..]);
+        rest = tail;
+    }
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// Split a line into its indentation, the first token, and the remainder.
+fn split_first_token(line: &str) -> (&str, &str, &str) {
+    let trimmed = line.trim_start_matches([' ', '\t']);
+    let indent = &line[..line.len() - trimmed.len()];
+    let end = trimmed.find([' ', '\t']).unwrap_or(trimmed.len());
+    let (token, remainder) = trimmed.split_at(end);
+    (indent, token, remainder)
+}
+
+/// Lowercase the command keyword at the start of the line.
+fn lowercase_keyword(line: &str) -> Cow<'_, str> {
+    let (indent, token, remainder) = split_first_token(line);
+    if token.bytes().any(|b| b.is_ascii_uppercase()) {
+        let lower = token.to_ascii_lowercase();
+        if KEYWORDS.contains(&lower.as_str()) {
+            return Cow::Owned(format!("{indent}{lower}{remainder}"));
+        }
+    }
+    Cow::Borrowed(line)
+}
+
+/// Insert a missing `name` keyword after `setoption`.
+fn insert_setoption_name(line: &str) -> Cow<'_, str> {
+    let (indent, token, remainder) = split_first_token(line);
+    if token == "setoption" {
+        let (_, name, _) = split_first_token(remainder);
+        if !name.is_empty() && name != "name" {
+            return Cow::Owned(format!("{indent}setoption name{remainder}"));
+        }
+    }
+    Cow::Borrowed(line)
+}
+
+/// Replace a `gameover` synonym by the protocol result.
+fn map_gameover_synonym<'a>(
+    line: &'a str,
+    synonyms: &[(Cow<'static, str>, GameStatus)],
+) -> Cow<'a, str> {
+    let (indent, token, remainder) = split_first_token(line);
+    if token == "gameover" {
+        let (_, word, rest) = split_first_token(remainder);
+        if rest.trim_start_matches([' ', '\t']).is_empty()
+            && let Some((_, status)) = synonyms.iter().find(|(synonym, _)| *synonym == word)
+        {
+            return Cow::Owned(format!("{indent}gameover {status}{rest}"));
+        }
+    }
+    Cow::Borrowed(line)
+}
+
+/// Apply a token replacement to the params of an `info` line, up to `string`.
+///
+/// `f` is called with the previous token and the token itself, and returns the
+/// replacement, if any.
+fn map_info_params<F>(line: &str, f: F) -> Cow<'_, str>
+where
+    F: Fn(&str, &str) -> Option<String>,
+{
+    let (indent, token, remainder) = split_first_token(line);
+    if token != "info" {
+        return Cow::Borrowed(line);
+    }
+    let mut out = format!("{indent}{token}");
+    let mut changed = false;
+    let mut prev = token;
+    let mut rest = remainder;
+    loop {
+        let (space, word, tail) = split_first_token(rest);
+        if word.is_empty() || word == "string" {
+            out.push_str(rest);
+            break;
+        }
+        out.push_str(space);
+        match f(prev, word) {
+            Some(replacement) => {
+                out.push_str(&replacement);
+                changed = true;
+            }
+            None => out.push_str(word),
+        }
+        prev = word;
+        rest = tail;
+    }
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Rewrite the `key=value` params of an `info` line as `key value`.
+fn split_info_key_value(line: &str) -> Cow<'_, str> {
+    map_info_params(line, |_, word| {
+        let (key, value) = word.split_once('=')?;
+        (!key.is_empty() && !value.is_empty())
+            .then(|| format!("{} {}", key, value.replace(':', " ")))
+    })
+}
+
+/// Strip a `%` after the number of `info hashfull` and `info cpuload`.
+fn strip_info_percent(line: &str) -> Cow<'_, str> {
+    map_info_params(line, |prev, word| {
+        if prev != "hashfull" && prev != "cpuload" {
+            return None;
+        }
+        let digits = word.strip_suffix('%')?;
+        (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| digits.to_string())
+    })
+}
+
+/// Replace in-line separators by line breaks.
+fn split_on(line: &str, sep: char) -> Cow<'_, str> {
+    if line.contains(sep) {
+        Cow::Owned(line.replace(sep, "\n"))
+    } else {
+        Cow::Borrowed(line)
+    }
+}
@@ -8,26 +8,121 @@
 //! - [`GuiMessage::parse_first_valid`]
 //! - [`EngineMessage::parse`]
 //! - [`EngineMessage::parse_first_valid`]
+//! - [`EngineMessage::parse_into`]
+//! - [`parse_gui_script`]
+//! - [`parse_both`]
+//!
+//! The `parse` functions implement the strict protocol. Variants which take
+//! [`ParseOptions`] are available as `parse_with` (first message) and
+//! `parse_all_with` (all messages).
 //!
-#![allow(clippy::result_large_err)]
-
 use core::str::FromStr;
 use haitaka_types::Move;
 use pest::Parser; // Parser trait
-use pest::error::Error as PestError;
+use pest::error::{Error as PestError, LineColLocation};
 use pest::iterators::{Pair, Pairs};
-use pest_derive::Parser; // Parser proc macro
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::time::Duration;
 
 use crate::engine::{
-    BestMoveParams, EngineMessage, IdParams, InfoParam, OptionParam, ScoreBound, StatusCheck,
+    BestMoveParams, CheckMateParams, EngineMessage, IdParams, InfoParam, OptionParam, ScoreBound,
+    StatusCheck,
 };
 use crate::gui::{EngineParams, GameStatus, GuiMessage, MateParam};
+use crate::moves::MoveList;
+use crate::options::ParseOptions;
+use crate::sfen::Sfen;
+
+// The grammar lives in a private module, so the generated `Rule` enum stays
+// an implementation detail of this crate.
+mod grammar {
+    use pest_derive::Parser; // Parser proc macro
+
+    #[derive(Parser)]
+    #[grammar = "usi.pest"]
+    pub(crate) struct UsiParser;
+}
+
+pub(crate) use grammar::{Rule, UsiParser};
+
+/// Errors returned by the parse functions.
+///
+/// The PEST error itself is not kept, since it refers to the crate-private grammar
+/// rules, so [`std::error::Error::source`] is always `None`. For a syntax error, the
+/// full PEST report is available through [`ParseError::verbose`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ParseError {
+    /// The input is not terminated by a newline. `line` is the unterminated last line,
+    /// truncated to [`ParseError::MAX_LINE_LEN`] characters.
+    MissingNewline { line: String },
+
+    /// The input could not be parsed. Line and column (1-based) give the location of the error.
+    Syntax {
+        line: usize,
+        col: usize,
+        message: String,
+        /// The full multi-line PEST error report, see [`ParseError::verbose`].
+        rendered: String,
+    },
+
+    /// The SFEN string is malformed (see [`crate::Sfen::new`]).
+    InvalidSfen(String),
+}
+
+impl ParseError {
+    /// The number of characters of the input kept in [`ParseError::MissingNewline`].
+    pub const MAX_LINE_LEN: usize = 40;
+
+    /// Convert a PEST error for the given input.
+    pub(crate) fn from_pest(err: PestError<Rule>, input: &str) -> Self {
+        if !input.trim_end_matches([' ', '\t']).ends_with(['\n', '\r']) {
+            let last = input.rsplit(['\n', '\r']).next().unwrap_or_default();
+            let mut line: String = last.chars().take(Self::MAX_LINE_LEN).collect();
+            if line.len() < last.len() {
+                line.push_str("...");
+            }
+            return Self::MissingNewline { line };
+        }
+        let (line, col) = match err.line_col {
+            LineColLocation::Pos(pos) => pos,
+            LineColLocation::Span(start, _) => start,
+        };
+        Self::Syntax {
+            line,
+            col,
+            message: err.variant.message().to_string(),
+            rendered: err.to_string(),
+        }
+    }
+
+    /// The full error report.
+    ///
+    /// For a syntax error this is PEST's multi-line rendering, which shows the
+    /// offending line with the error location marked. `Display` gives a one-line
+    /// message, which is more suitable for logs.
+    pub fn verbose(&self) -> String {
+        match self {
+            Self::Syntax { rendered, .. } => rendered.clone(),
+            _ => self.to_string(),
+        }
+    }
+}
 
-#[derive(Parser)]
-#[grammar = "usi.pest"]
-struct UsiParser;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingNewline { line } => {
+                write!(f, "expected newline-terminated USI message, got {:?}", line)
+            }
+            Self::Syntax {
+                line, col, message, ..
+            } => write!(f, "syntax error at {}:{}: {}", line, col, message),
+            Self::InvalidSfen(sfen) => write!(f, "invalid SFEN \"{}\"", sfen),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 /// This function visualizes the PEST parse tree of any input.
 pub fn dbg(s: &str) {
@@ -57,7 +152,21 @@ macro_rules! as_string {
     };
 }
 
-/// Convert "<empty>" into Some(""). Used in parsing `option ... default <empty>`.
+/// Extract the words of a PEST Span as `String`, joined by single spaces.
+/// Used for values that are a sequence of fields, where the separating whitespace
+/// carries no meaning (like the fields of an SFEN).
+macro_rules! as_words {
+    ($sp:ident) => {
+        $sp.as_span()
+            .as_str()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+}
+
+/// Convert "<empty>" into Some(""). Used in parsing `option ... default <empty>` and
+/// `setoption ... value <empty>`.
 macro_rules! convert_empty {
     ($s:ident) => {
         if $s.eq_ignore_ascii_case("<empty>") {
@@ -95,13 +204,79 @@ impl GuiMessage {
     /// let msg = GuiMessage::parse(input).unwrap();
     /// assert_eq!(msg, GuiMessage::Usi);
     /// ```
-    pub fn parse(input: &str) -> Result<Self, PestError<Rule>> {
-        match UsiParser::parse(Rule::start, input) {
-            Ok(pairs) => Ok(Self::inner_parse(pairs.into_iter().next().unwrap())),
-            Err(err) => Err(err),
+    pub fn parse(input: impl AsRef<str>) -> Result<Self, ParseError> {
+        Self::parse_with(input, &ParseOptions::default())
+    }
+
+    /// Parse one USI message, sent by the GUI and received by the Engine, using the given options.
+    ///
+    /// See [`GuiMessage::parse`] and [`ParseOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let options = ParseOptions { case_insensitive: true, ..ParseOptions::default() };
+    /// let msg = GuiMessage::parse_with("USI\n", &options).unwrap();
+    /// assert_eq!(msg, GuiMessage::Usi);
+    /// ```
+    pub fn parse_with(input: impl AsRef<str>, options: &ParseOptions) -> Result<Self, ParseError> {
+        let input = options.preprocess(input.as_ref());
+        match UsiParser::parse(Rule::start, &input) {
+            Ok(pairs) => Ok(Self::inner_parse(
+                pairs.into_iter().next().unwrap(),
+                options,
+            )),
+            Err(err) => Err(ParseError::from_pest(err, &input)),
+        }
+    }
+
+    /// Parse all messages in the input, using the given options.
+    ///
+    /// Unlike [`GuiMessage::parse_with`], which only returns the first message, this returns
+    /// every message, including `Unknown`s. This is the way to use options with multi-line
+    /// input, or with the `separator` option.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let options = ParseOptions { separator: Some(';'), ..ParseOptions::default() };
+    /// let msgs = GuiMessage::parse_all_with("usinewgame; isready\n", &options).unwrap();
+    /// assert_eq!(msgs, vec![GuiMessage::UsiNewGame, GuiMessage::IsReady]);
+    /// ```
+    pub fn parse_all_with(
+        input: impl AsRef<str>,
+        options: &ParseOptions,
+    ) -> Result<Vec<Self>, ParseError> {
+        let input = options.preprocess(input.as_ref());
+        match UsiParser::parse(Rule::start, &input) {
+            Ok(pairs) => Ok(pairs.map(|pair| Self::inner_parse(pair, options)).collect()),
+            Err(err) => Err(ParseError::from_pest(err, &input)),
         }
     }
 
+    /// Parse the first message of the input and return it with the number of bytes consumed.
+    ///
+    /// The count includes the terminating newline of the message, so that a caller
+    /// reading from a streaming buffer can advance the buffer past it. Only the first
+    /// (non-blank) line needs to be complete. If the input starts with a junk prefix,
+    /// only the prefix is consumed (and returned as `Unknown`), as with [`GuiMessage::parse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let input = "usi\nisready\n";
+    /// let (msg, n) = GuiMessage::parse_prefix(input).unwrap();
+    /// assert_eq!((msg, n), (GuiMessage::Usi, 4));
+    /// let (msg, _) = GuiMessage::parse_prefix(&input[n..]).unwrap();
+    /// assert_eq!(msg, GuiMessage::IsReady);
+    /// ```
+    pub fn parse_prefix(input: impl AsRef<str>) -> Result<(Self, usize), ParseError> {
+        parse_prefix_with(input.as_ref(), Self::inner_parse)
+    }
+
     /// Parses the input and returns the first valid protocol GUI message, skipping Unknowns.
     /// Returns `None` if no valid message is found.
     ///
@@ -117,11 +292,32 @@ impl GuiMessage {
     /// let msg = GuiMessage::parse_first_valid(input).unwrap();
     /// assert_eq!(msg, GuiMessage::UsiNewGame);
     /// ```
-    pub fn parse_first_valid(input: &str) -> Option<Self> {
-        GuiMessageStream::new(input).find(|msg| !matches!(msg, GuiMessage::Unknown(_)))
+    pub fn parse_first_valid(input: impl AsRef<str>) -> Option<Self> {
+        GuiMessageStream::new(input.as_ref()).find(|msg| !matches!(msg, GuiMessage::Unknown(_)))
+    }
+
+    /// Parse one line, appending a newline if it is missing.
+    ///
+    /// This never fails: input that does not conform to the protocol is returned as
+    /// `Unknown`. If the line contains multiple messages, only the first one is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// assert_eq!(GuiMessage::from_line("usi"), GuiMessage::Usi);
+    /// assert!(GuiMessage::from_line("yoho").is_unknown());
+    /// ```
+    pub fn from_line(line: &str) -> Self {
+        let result = if line.ends_with(['\n', '\r']) {
+            Self::parse(line)
+        } else {
+            Self::parse(format!("{line}\n"))
+        };
+        result.unwrap_or_else(|_| Self::Unknown(line.to_string()))
     }
 
-    fn inner_parse(p: Pair<'_, Rule>) -> Self {
+    fn inner_parse(p: Pair<'_, Rule>, options: &ParseOptions) -> Self {
         match p.as_rule() {
             Rule::usi => Self::parse_usi(),
             Rule::debug => Self::parse_debug(p),
@@ -130,7 +326,7 @@ impl GuiMessage {
             Rule::register_user => Self::parse_register(p),
             Rule::usinewgame => Self::parse_usinewgame(),
             Rule::position => Self::parse_position(p),
-            Rule::go => Self::parse_go(p),
+            Rule::go => Self::parse_go(p, options),
             Rule::stop => Self::parse_stop(),
             Rule::ponderhit => Self::parse_ponderhit(),
             Rule::gameover => Self::parse_gameover(p),
@@ -170,7 +366,8 @@ impl GuiMessage {
                     name = as_string!(sp);
                 }
                 Rule::setoption_value => {
-                    value = Some(as_string!(sp));
+                    let s = as_str!(sp);
+                    value = convert_empty!(s);
                 }
                 _ => unreachable!(),
             }
@@ -212,23 +409,24 @@ impl GuiMessage {
     // position
     fn parse_position(pair: Pair<Rule>) -> Self {
         let mut sfen: Option<String> = None;
-        let mut moves: Option<Vec<Move>> = None;
+        let mut moves: Option<MoveList> = None;
+        let text = as_string!(pair);
         for sp in pair.into_inner() {
             match sp.as_rule() {
                 Rule::startpos => {
                     assert!(sfen.is_none());
                 }
                 Rule::sfenpos => {
-                    sfen = Some(
-                        as_str!(sp)
-                            .strip_prefix("sfen ")
-                            .unwrap()
-                            .trim()
-                            .to_string(),
-                    );
+                    let words = as_words!(sp);
+                    let s = words.strip_prefix("sfen ").unwrap();
+                    // the grammar checks the fields, but not that each rank has 9 files
+                    if Sfen::new(s).is_err() {
+                        return Self::Unknown(text);
+                    }
+                    sfen = Some(s.to_string());
                 }
                 Rule::moves => {
-                    moves = Some(parse_moves(sp));
+                    moves = Some(parse_moves(sp, usize::MAX));
                 }
                 _ => unreachable!(),
             }
@@ -237,13 +435,13 @@ impl GuiMessage {
     }
 
     // go
-    fn parse_go(pair: Pair<Rule>) -> Self {
+    fn parse_go(pair: Pair<Rule>, options: &ParseOptions) -> Self {
         let mut params = EngineParams::new();
 
         for sp in pair.into_inner() {
             match sp.as_rule() {
                 Rule::searchmoves => {
-                    params = params.searchmoves(parse_moves(sp));
+                    params = params.searchmoves(parse_moves(sp, options.max_moves));
                 }
                 Rule::depth => {
                     params = params.depth(parse_digits::<u16>(sp));
@@ -254,6 +452,11 @@ impl GuiMessage {
                 Rule::mate => {
                     for spi in sp.into_inner() {
                         match spi.as_rule() {
+                            Rule::millisecs if options.mate_in_moves => {
+                                let n = parse_number::<u64>(as_str!(spi));
+                                params =
+                                    params.mate(MateParam::Moves(n.try_into().unwrap_or(u16::MAX)))
+                            }
                             Rule::millisecs => {
                                 params = params.mate(MateParam::Timeout(parse_millisecs(spi)))
                             }
@@ -313,6 +516,7 @@ impl GuiMessage {
                 _ => unreachable!(),
             }
         }
+        // the grammar requires a result, so a bare `gameover` never gets here
         unreachable!()
     }
 
@@ -323,6 +527,12 @@ impl GuiMessage {
 }
 
 /// The GuiMessageStream struct enables iteration over a multi-line text string.
+///
+/// Blank (empty or whitespace-only) lines are skipped.
+///
+/// Cloning a stream is cheap and gives an independent iterator which continues
+/// from the same point.
+#[derive(Clone)]
 pub struct GuiMessageStream<'a> {
     /// Inner PEST iterator over grammar Rules
     pairs: Pairs<'a, Rule>,
@@ -331,36 +541,144 @@ pub struct GuiMessageStream<'a> {
 impl<'a> GuiMessageStream<'a> {
     /// Create a new `GuiMessageStream` from an input string.
     ///
-    /// SAFETY: Since the grammar is designed to process any input, this should never fail.
-    pub fn new(input: &'a str) -> Self {
+    /// The stream borrows the input, so this takes a reference to anything that
+    /// can be viewed as a `str` (like `&str`, `&String` or `&Cow<str>`).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the input string is not newline terminated. Unlike
+    /// [`EngineMessageStream::new`], a trailing fragment is not split off, since the GUI
+    /// side is normally a script or log, and not a pipe read in chunks. Use
+    /// [`GuiMessageStream::try_parse`] to get an error instead.
+    pub fn new<S: AsRef<str> + ?Sized>(input: &'a S) -> Self {
         Self::parse(input)
     }
 
     /// Parse a multi-line input string and return a GuiMessageStream instance.
     ///
-    /// SAFETY: Since the parser should be able to handle any input, this should never fail.
-    pub fn parse(input: &'a str) -> Self {
+    /// # Panics
+    ///
+    /// This function will panic if the input string is not newline terminated
+    /// (see [`GuiMessageStream::new`]).
+    pub fn parse<S: AsRef<str> + ?Sized>(input: &'a S) -> Self {
         Self::try_parse(input).expect("Internal error: Failed to initialize UsiParser.")
     }
 
-    pub fn try_parse(input: &'a str) -> Result<Self, PestError<Rule>> {
-        let pairs = UsiParser::parse(Rule::start, input);
-        match pairs {
+    /// Parse a multi-line input string and return a GuiMessageStream instance.
+    ///
+    /// Unlike [`GuiMessageStream::parse`], this returns an error if the input
+    /// does not end with a line terminator.
+    pub fn try_parse<S: AsRef<str> + ?Sized>(input: &'a S) -> Result<Self, ParseError> {
+        let input = input.as_ref();
+        match UsiParser::parse(Rule::start, input) {
             Ok(pairs) => Ok(Self { pairs }),
-            Err(err) => Err(err),
+            Err(err) => Err(ParseError::from_pest(err, input)),
         }
     }
+
+    /// Collect all messages, stopping at the first `Unknown`.
+    ///
+    /// Blank lines are skipped. If all messages are valid, they are returned. Otherwise the
+    /// valid messages before the first `Unknown` are returned together with the offending
+    /// text (without its line terminator).
+    pub fn try_collect_valid(self) -> Result<Vec<GuiMessage>, (Vec<GuiMessage>, String)> {
+        let mut msgs = Vec::new();
+        for msg in self {
+            match msg {
+                GuiMessage::Unknown(s) => {
+                    return Err((msgs, s.trim_end_matches(['\n', '\r']).to_string()));
+                }
+                msg => msgs.push(msg),
+            }
+        }
+        Ok(msgs)
+    }
 }
 
 impl Iterator for GuiMessageStream<'_> {
     type Item = GuiMessage;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(pair) = self.pairs.by_ref().next() {
-            let res = GuiMessage::inner_parse(pair);
-            return Some(res);
-        }
-        None
+        let pair = self
+            .pairs
+            .by_ref()
+            .find(|pair| !pair.as_str().trim().is_empty())?;
+        Some(GuiMessage::inner_parse(pair, &ParseOptions::default()))
+    }
+}
+
+/// Parse a script of GUI commands, returning each message paired with its 1-based line number.
+///
+/// This is intended for test-suite runners and similar tools that need to report errors
+/// like "line 42: unknown command". Blank lines and leading indentation are skipped.
+/// Unlike [`GuiMessage::parse`], the last line does not need to be terminated by a newline.
+/// A line with a junk prefix (like `yoho usi`) yields both an `Unknown` and the valid message,
+/// with the same line number.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let script = "usi\n\n  isready\nfoo bar";
+/// let msgs = parse_gui_script(script);
+/// assert_eq!(msgs[0], (1, GuiMessage::Usi));
+/// assert_eq!(msgs[1], (3, GuiMessage::IsReady));
+/// assert_eq!(msgs[2].0, 4);
+/// ```
+pub fn parse_gui_script(input: impl AsRef<str>) -> Vec<(usize, GuiMessage)> {
+    let input = input.as_ref();
+    let input = if input.ends_with(['\n', '\r']) {
+        input.to_string()
+    } else {
+        format!("{input}\n")
+    };
+    let pairs = UsiParser::parse(Rule::start, &input)
+        .expect("Internal error: Failed to initialize UsiParser.");
+    pairs
+        .filter(|pair| !pair.as_str().trim().is_empty())
+        .map(|pair| {
+            let (line, _) = pair.as_span().start_pos().line_col();
+            (
+                line,
+                GuiMessage::inner_parse(pair, &ParseOptions::default()),
+            )
+        })
+        .collect()
+}
+
+/// Parse a line as both a GUI and an Engine message.
+///
+/// Returns the result of each parser, or `None` for the side on which the line is
+/// `Unknown`. This is intended for log analyzers that do not know in which direction
+/// a line was sent. Like [`parse_gui_script`], the line does not need to be terminated
+/// by a newline. Only the first message in the input is considered.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// assert_eq!(parse_both("usiok"), (None, Some(EngineMessage::UsiOk)));
+/// assert_eq!(parse_both("isready\n"), (Some(GuiMessage::IsReady), None));
+/// ```
+pub fn parse_both(input: &str) -> (Option<GuiMessage>, Option<EngineMessage>) {
+    let input = if input.ends_with(['\n', '\r']) {
+        input.to_string()
+    } else {
+        format!("{input}\n")
+    };
+    let gui = GuiMessage::parse(&input)
+        .ok()
+        .filter(|msg| !msg.is_unknown());
+    let engine = EngineMessage::parse(&input)
+        .ok()
+        .filter(|msg| !msg.is_unknown());
+    (gui, engine)
+}
+
+impl From<&str> for GuiMessage {
+    /// See [`GuiMessage::from_line`].
+    fn from(line: &str) -> Self {
+        Self::from_line(line)
     }
 }
 
@@ -400,34 +718,164 @@ impl EngineMessage {
     ///     )
     /// );
     /// ```
-    pub fn parse(input: &str) -> Result<Self, PestError<Rule>> {
+    pub fn parse(input: impl AsRef<str>) -> Result<Self, ParseError> {
+        Self::parse_with(input, &ParseOptions::default())
+    }
+
+    /// Parse one USI message, sent by the Engine and received by the GUI, using the given options.
+    ///
+    /// See [`EngineMessage::parse`] and [`ParseOptions`].
+    pub fn parse_with(input: impl AsRef<str>, options: &ParseOptions) -> Result<Self, ParseError> {
+        let input = options.preprocess(input.as_ref());
+        match UsiParser::parse(Rule::start, &input) {
+            Ok(pairs) => Ok(Self::inner_parse(
+                pairs.into_iter().next().unwrap(),
+                options,
+            )),
+            Err(err) => Err(ParseError::from_pest(err, &input)),
+        }
+    }
+
+    /// Parse all messages in the input, using the given options.
+    ///
+    /// Unlike [`EngineMessage::parse_with`], which only returns the first message, this returns
+    /// every message, including `Unknown`s. This is the way to use options with multi-line
+    /// input, or with the `separator` option.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let options = ParseOptions { max_moves: 1, ..ParseOptions::default() };
+    /// let msgs = EngineMessage::parse_all_with("info pv 7g7f 3c3d\nreadyok\n", &options).unwrap();
+    /// assert_eq!(msgs[0], EngineMessage::Info(vec![InfoParam::Pv("7g7f".parse().unwrap())]));
+    /// assert_eq!(msgs[1], EngineMessage::ReadyOk);
+    /// ```
+    pub fn parse_all_with(
+        input: impl AsRef<str>,
+        options: &ParseOptions,
+    ) -> Result<Vec<Self>, ParseError> {
+        let input = options.preprocess(input.as_ref());
+        match UsiParser::parse(Rule::start, &input) {
+            Ok(pairs) => Ok(pairs.map(|pair| Self::inner_parse(pair, options)).collect()),
+            Err(err) => Err(ParseError::from_pest(err, &input)),
+        }
+    }
+
+    /// Parse one USI message, reusing the storage of `buf` for the params of an `info` message.
+    ///
+    /// This is meant for hot loops which parse many `info` lines and would otherwise
+    /// allocate a fresh `Vec<InfoParam>` per line. The contract is:
+    ///
+    /// - `buf` is always cleared first. Its capacity is kept.
+    /// - If the input is an `info` message, its params are pushed onto `buf` and
+    ///   `EngineMessage::Info(Vec::new())` is returned as a marker (an empty `Vec`
+    ///   does not allocate). The params stay valid until the next call with the
+    ///   same buffer.
+    /// - Any other message is returned as by [`EngineMessage::parse`], leaving `buf` empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let mut buf = Vec::new();
+    /// for line in ["info depth 1 nodes 10\n", "info depth 2 nodes 80\n"] {
+    ///     let msg = EngineMessage::parse_into(line, &mut buf).unwrap();
+    ///     assert_eq!(msg, EngineMessage::Info(vec![]));
+    ///     assert_eq!(buf.len(), 2);
+    /// }
+    /// assert_eq!(buf[1], InfoParam::Nodes(80));
+    /// ```
+    pub fn parse_into(
+        input: impl AsRef<str>,
+        buf: &mut Vec<InfoParam>,
+    ) -> Result<Self, ParseError> {
+        let input = input.as_ref();
+        let options = ParseOptions::default();
+        buf.clear();
         match UsiParser::parse(Rule::start, input) {
-            Ok(pairs) => Ok(Self::inner_parse(pairs.into_iter().next().unwrap())),
-            Err(err) => Err(err),
+            Ok(pairs) => {
+                let pair = pairs.into_iter().next().unwrap();
+                if pair.as_rule() == Rule::info {
+                    Self::parse_info_params(pair, &options, buf);
+                    Ok(EngineMessage::Info(Vec::new()))
+                } else {
+                    Ok(Self::inner_parse(pair, &options))
+                }
+            }
+            Err(err) => Err(ParseError::from_pest(err, input)),
         }
     }
 
+    /// Parse the first message of the input and return it with the number of bytes consumed.
+    ///
+    /// The count includes the terminating newline of the message, so that a caller
+    /// reading from a streaming buffer can advance the buffer past it. Only the first
+    /// (non-blank) line needs to be complete. If the input starts with a junk prefix,
+    /// only the prefix is consumed (and returned as `Unknown`), as with [`EngineMessage::parse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let (msg, n) = EngineMessage::parse_prefix("readyok\r\nbestmo").unwrap();
+    /// assert_eq!((msg, n), (EngineMessage::ReadyOk, 9));
+    /// ```
+    pub fn parse_prefix(input: impl AsRef<str>) -> Result<(Self, usize), ParseError> {
+        parse_prefix_with(input.as_ref(), Self::inner_parse)
+    }
+
     /// Parses the input and returns the first valid protocol Engine message, skipping Unknowns.
     /// Returns `None` if no valid Engine message is found.
     ///
-    /// # Panics
+    /// A trailing fragment without line terminator is ignored (see
+    /// [`EngineMessageStream::incomplete`]).
     ///
-    /// This function will panic if the input string is not newline terminated.
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = EngineMessage::parse_first_valid("yo\nreadyok\n");
+    /// assert_eq!(msg, Some(EngineMessage::ReadyOk));
+    /// assert_eq!(EngineMessage::parse_first_valid("usiok"), None);
+    /// ```
+    pub fn parse_first_valid(input: impl AsRef<str>) -> Option<Self> {
+        EngineMessageStream::new(input.as_ref())
+            .find(|msg| !matches!(msg, EngineMessage::Unknown(_)))
+    }
+
+    /// Parse one line, appending a newline if it is missing.
+    ///
+    /// This never fails: input that does not conform to the protocol is returned as
+    /// `Unknown`. If the line contains multiple messages, only the first one is returned.
     ///
-    pub fn parse_first_valid(input: &str) -> Option<Self> {
-        EngineMessageStream::new(input).find(|msg| !matches!(msg, EngineMessage::Unknown(_)))
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// assert_eq!(EngineMessage::from_line("usiok"), EngineMessage::UsiOk);
+    /// assert!(EngineMessage::from_line("yoho").is_unknown());
+    /// ```
+    pub fn from_line(line: &str) -> Self {
+        let result = if line.ends_with(['\n', '\r']) {
+            Self::parse(line)
+        } else {
+            Self::parse(format!("{line}\n"))
+        };
+        result.unwrap_or_else(|_| Self::Unknown(line.to_string()))
     }
 
-    fn inner_parse(p: Pair<'_, Rule>) -> Self {
+    fn inner_parse(p: Pair<'_, Rule>, options: &ParseOptions) -> Self {
         match p.as_rule() {
             Rule::id => Self::parse_id(p),
             Rule::usiok => Self::parse_usiok(),
             Rule::readyok => Self::parse_readyok(),
-            Rule::bestmove => Self::parse_bestmove(p),
+            Rule::bestmove => Self::parse_bestmove(p, options),
+            Rule::checkmate => Self::parse_checkmate(p),
             Rule::copyprotection => Self::parse_copyprotection(p),
             Rule::registration => Self::parse_registration(p),
             Rule::option => Self::parse_option(p),
-            Rule::info => Self::parse_info(p),
+            Rule::info => Self::parse_info(p, options),
             _ => Self::parse_unknown(p.as_str()),
         }
     }
@@ -460,9 +908,10 @@ impl EngineMessage {
     }
 
     // bestmove
-    fn parse_bestmove(pair: Pair<Rule>) -> Self {
+    fn parse_bestmove(pair: Pair<Rule>, options: &ParseOptions) -> Self {
         let mut bestmove: Option<Move> = None;
         let mut ponder: Option<Move> = None;
+        let text = as_string!(pair);
 
         for sp in pair.into_inner() {
             match sp.as_rule() {
@@ -474,6 +923,13 @@ impl EngineMessage {
                 }
                 Rule::resign => return EngineMessage::BestMove(BestMoveParams::Resign),
                 Rule::win => return EngineMessage::BestMove(BestMoveParams::Win),
+                Rule::no_move => {
+                    return if options.no_move_tokens.iter().any(|t| t == as_str!(sp)) {
+                        EngineMessage::BestMove(BestMoveParams::None)
+                    } else {
+                        EngineMessage::Unknown(text)
+                    };
+                }
                 _ => unreachable!(),
             }
         }
@@ -485,6 +941,20 @@ impl EngineMessage {
         }
     }
 
+    // checkmate
+    fn parse_checkmate(pair: Pair<Rule>) -> Self {
+        let sp = pair.into_inner().next().unwrap();
+        let params = match sp.as_rule() {
+            Rule::nomate => CheckMateParams::NoMate,
+            Rule::timeout => CheckMateParams::TimeOut,
+            Rule::notimplemented => CheckMateParams::NotImplemented,
+            // like the moves of a position, a mate solution is never truncated
+            Rule::moves => CheckMateParams::Mate(parse_moves(sp, usize::MAX)),
+            _ => unreachable!(),
+        };
+        EngineMessage::CheckMate(params)
+    }
+
     // copyprotection
     fn parse_copyprotection(pair: Pair<Rule>) -> Self {
         let state = Self::parse_status_check(pair);
@@ -514,11 +984,12 @@ impl EngineMessage {
 
     // option
     fn parse_option(pair: Pair<Rule>) -> Self {
+        let text = as_string!(pair);
         if let Some(sp) = pair.into_inner().next() {
             match sp.as_rule() {
                 Rule::check_option => return Self::parse_check_option(sp),
-                Rule::spin_option => return Self::parse_spin_option(sp),
-                Rule::combo_option => return Self::parse_combo_option(sp),
+                Rule::spin_option => return Self::parse_spin_option(sp, &text),
+                Rule::combo_option => return Self::parse_combo_option(sp, &text),
                 Rule::string_option => return Self::parse_string_option(sp),
                 Rule::button_option => return Self::parse_button_option(sp),
                 Rule::filename_option => return Self::parse_filename_option(sp),
@@ -547,7 +1018,9 @@ impl EngineMessage {
     }
 
     // option name ... type spin ...
-    fn parse_spin_option(pair: Pair<Rule>) -> Self {
+    //
+    // The grammar takes the sub-keys in any order, so a repeated key is only caught here.
+    fn parse_spin_option(pair: Pair<Rule>, text: &str) -> Self {
         let mut name: Option<String> = None;
         let mut default: Option<i32> = None;
         let mut min: Option<i32> = None;
@@ -556,6 +1029,9 @@ impl EngineMessage {
         for sp in pair.into_inner() {
             match sp.as_rule() {
                 Rule::option_name => name = Some(parse_tokens(sp)),
+                Rule::spin_default if default.is_some() => return Self::parse_unknown(text),
+                Rule::spin_min if min.is_some() => return Self::parse_unknown(text),
+                Rule::spin_max if max.is_some() => return Self::parse_unknown(text),
                 Rule::spin_default => default = Some(parse_integer::<i32>(sp)),
                 Rule::spin_min => min = Some(parse_integer::<i32>(sp)),
                 Rule::spin_max => max = Some(parse_integer::<i32>(sp)),
@@ -576,7 +1052,9 @@ impl EngineMessage {
     }
 
     // option name ... type combo ...
-    fn parse_combo_option(pair: Pair<Rule>) -> Self {
+    //
+    // Like the spin sub-keys, a repeated `default` is only caught here.
+    fn parse_combo_option(pair: Pair<Rule>, text: &str) -> Self {
         let mut name: Option<String> = None;
         let mut default: Option<String> = None;
         let mut vars: Vec<String> = Vec::new();
@@ -584,8 +1062,9 @@ impl EngineMessage {
         for sp in pair.into_inner() {
             match sp.as_rule() {
                 Rule::option_name => name = Some(parse_tokens(sp)),
-                Rule::combo_default => default = Some(parse_tokens(sp)),
-                Rule::var_token => vars.push(parse_tokens(sp)),
+                Rule::combo_default if default.is_some() => return Self::parse_unknown(text),
+                Rule::combo_default => default = Some(as_string!(sp).to_owned()),
+                Rule::var_token => vars.push(as_string!(sp).to_owned()),
                 _ => (),
             }
         }
@@ -663,8 +1142,13 @@ impl EngineMessage {
     }
 
     // info
-    fn parse_info(pair: Pair<Rule>) -> Self {
+    fn parse_info(pair: Pair<Rule>, options: &ParseOptions) -> Self {
         let mut v: Vec<InfoParam> = Vec::<InfoParam>::new();
+        Self::parse_info_params(pair, options, &mut v);
+        EngineMessage::Info(v)
+    }
+
+    fn parse_info_params(pair: Pair<Rule>, options: &ParseOptions, v: &mut Vec<InfoParam>) {
         for sp in pair.into_inner() {
             let info: InfoParam = match sp.as_rule() {
                 Rule::info_depth => InfoParam::Depth(parse_digits::<u16>(sp)),
@@ -675,30 +1159,37 @@ impl EngineMessage {
                 Rule::info_currmove => InfoParam::CurrMove(parse_move(sp)),
                 Rule::info_hashfull => InfoParam::HashFull(parse_digits::<u16>(sp)),
                 Rule::info_nps => InfoParam::Nps(parse_digits::<u64>(sp)),
+                Rule::info_tbhits => InfoParam::TbHits(parse_digits::<u64>(sp)),
+                Rule::info_sbhits => InfoParam::SbHits(parse_digits::<u64>(sp)),
                 Rule::info_cpuload => InfoParam::CpuLoad(parse_digits::<u16>(sp)),
                 Rule::info_multipv => InfoParam::MultiPv(parse_digits::<u16>(sp)),
                 Rule::info_string => InfoParam::String(parse_tokens(sp)),
-                Rule::info_pv => InfoParam::Pv(parse_moves(sp)),
-                Rule::info_refutation => InfoParam::Refutation(parse_moves(sp)),
-                Rule::info_currline => Self::parse_currline(sp),
+                Rule::info_pv => {
+                    let pv = parse_moves(sp, options.max_moves);
+                    if pv.is_empty() {
+                        continue;
+                    }
+                    InfoParam::Pv(pv)
+                }
+                Rule::info_refutation => InfoParam::Refutation(parse_moves(sp, options.max_moves)),
+                Rule::info_currline => Self::parse_currline(sp, options),
                 Rule::info_score_cp => Self::parse_score_cp(sp),
                 Rule::info_score_mate => Self::parse_score_mate(sp),
                 _ => unreachable!(),
             };
             v.push(info);
         }
-        EngineMessage::Info(v)
     }
 
     // info currline ...
-    fn parse_currline(pair: Pair<Rule>) -> InfoParam {
+    fn parse_currline(pair: Pair<Rule>, options: &ParseOptions) -> InfoParam {
         let mut cpu_nr: Option<u16> = None;
-        let mut line: Vec<Move> = Vec::<Move>::new();
+        let mut line = MoveList::new();
 
         for sp in pair.into_inner() {
             match sp.as_rule() {
                 Rule::cpunr => cpu_nr = Some(parse_digits::<u16>(sp)),
-                Rule::moves => line = parse_moves(sp),
+                Rule::moves => line = parse_moves(sp, options.max_moves),
                 _ => unreachable!(),
             }
         }
@@ -714,12 +1205,7 @@ impl EngineMessage {
             match sp.as_rule() {
                 Rule::integer => {
                     let s = as_str!(sp); // Extract the string representation
-                    v = Some(s.parse::<i32>().unwrap_or_else(|err| {
-                        unreachable!(
-                            "PEST grammar bug: failed to parse integer '{}': {:?}",
-                            s, err
-                        )
-                    }));
+                    v = Some(parse_number::<i32>(s));
                 }
                 Rule::lowerbound => bound = ScoreBound::Lower,
                 Rule::upperbound => bound = ScoreBound::Upper,
@@ -743,7 +1229,7 @@ impl EngineMessage {
             match sp.as_rule() {
                 Rule::integer => {
                     let s = as_str!(sp); // Extract the string representation
-                    v = Some(s.parse::<i32>().unwrap());
+                    v = Some(parse_number::<i32>(s));
                 }
                 Rule::plus => bound = ScoreBound::MatePlus,
                 Rule::minus => bound = ScoreBound::MateMin,
@@ -757,32 +1243,188 @@ impl EngineMessage {
 }
 
 /// The EngineMessageStream struct enables iteration over a multi-line text string.
+///
+/// Blank (empty or whitespace-only) lines are skipped.
+///
+/// When reading from a pipe, a chunk of input may end in the middle of a line. A
+/// stream created with [`EngineMessageStream::new`] only parses the complete lines,
+/// and keeps the trailing fragment, which the caller can prepend to the next chunk
+/// (see [`EngineMessageStream::incomplete`]).
+///
+/// Cloning a stream is cheap and gives an independent iterator which continues
+/// from the same point.
+#[derive(Clone)]
 pub struct EngineMessageStream<'a> {
-    /// Inner PEST iterator over grammar Rules
-    pairs: Pairs<'a, Rule>,
+    /// Inner PEST iterator over grammar Rules, `None` if there are no complete lines
+    pairs: Option<Pairs<'a, Rule>>,
+
+    /// Trailing input without line terminator
+    incomplete: &'a str,
 }
 
 impl<'a> EngineMessageStream<'a> {
     /// Create a new `EngineMessageStream` from an input string.
     ///
-    /// SAFETY: Since the grammar is designed to process any input, this should never fail.
-    pub fn new(input: &'a str) -> Self {
+    /// The stream borrows the input, so this takes a reference to anything that
+    /// can be viewed as a `str` (like `&str`, `&String` or `&Cow<str>`).
+    ///
+    /// Input after the last line terminator is split off and kept as
+    /// [`EngineMessageStream::incomplete`], so this never fails.
+    pub fn new<S: AsRef<str> + ?Sized>(input: &'a S) -> Self {
         Self::parse(input)
     }
 
     /// Parse an input string and return a new `EngineMessageStream`.
     ///
-    /// SAFETY: Since the grammar is designed to process any input, this should never fail.
-    pub fn parse(input: &'a str) -> Self {
-        Self::try_parse(input).expect("Internal error: Failed to initialize UsiParser.")
+    /// Input after the last line terminator is not parsed, but kept as
+    /// [`EngineMessageStream::incomplete`].
+    ///
+    /// SAFETY: Since the grammar is designed to process any newline-terminated input,
+    /// this should never fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let mut stream = EngineMessageStream::new("usiok\nbestmo");
+    /// assert_eq!(stream.next(), Some(EngineMessage::UsiOk));
+    /// assert_eq!(stream.next(), None);
+    /// assert_eq!(stream.incomplete(), "bestmo");
+    /// ```
+    pub fn parse<S: AsRef<str> + ?Sized>(input: &'a S) -> Self {
+        let input = input.as_ref();
+        let end = input.rfind(['\n', '\r']).map_or(0, |i| i + 1);
+        let (complete, incomplete) = input.split_at(end);
+        let pairs = (!complete.is_empty()).then(|| {
+            UsiParser::parse(Rule::start, complete)
+                .expect("Internal error: Failed to initialize UsiParser.")
+        });
+        Self { pairs, incomplete }
     }
 
-    pub fn try_parse(input: &'a str) -> Result<Self, PestError<Rule>> {
-        let pairs = UsiParser::parse(Rule::start, input);
-        match pairs {
-            Ok(pairs) => Ok(Self { pairs }),
-            Err(err) => Err(err),
+    /// Parse an input string and return a new `EngineMessageStream`.
+    ///
+    /// Unlike [`EngineMessageStream::parse`], this returns an error if the input
+    /// does not end with a line terminator.
+    pub fn try_parse<S: AsRef<str> + ?Sized>(input: &'a S) -> Result<Self, ParseError> {
+        let input = input.as_ref();
+        match UsiParser::parse(Rule::start, input) {
+            Ok(pairs) => Ok(Self {
+                pairs: Some(pairs),
+                incomplete: "",
+            }),
+            Err(err) => Err(ParseError::from_pest(err, input)),
+        }
+    }
+
+    /// The trailing input after the last line terminator, which was not parsed.
+    ///
+    /// This is empty if the input ended with a line terminator.
+    pub fn incomplete(&self) -> &'a str {
+        self.incomplete
+    }
+
+    /// Collect all messages, stopping at the first `Unknown`.
+    ///
+    /// Blank lines are skipped. If all messages are valid, they are returned. Otherwise the
+    /// valid messages before the first `Unknown` are returned together with the offending
+    /// text (without its line terminator).
+    pub fn try_collect_valid(self) -> Result<Vec<EngineMessage>, (Vec<EngineMessage>, String)> {
+        let mut msgs = Vec::new();
+        for msg in self {
+            match msg {
+                EngineMessage::Unknown(s) => {
+                    return Err((msgs, s.trim_end_matches(['\n', '\r']).to_string()));
+                }
+                msg => msgs.push(msg),
+            }
         }
+        Ok(msgs)
+    }
+
+    /// Iterate over the messages up to and including the first `bestmove`.
+    ///
+    /// This is the common client loop of processing `info` lines until the search ends.
+    /// Iteration stops right after the `bestmove`, without parsing any trailing lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let input = "info depth 1\ninfo depth 2\nbestmove 7g7f\ninfo depth 1\n";
+    /// let msgs: Vec<EngineMessage> = EngineMessageStream::new(input).until_bestmove().collect();
+    /// assert_eq!(msgs.len(), 3);
+    /// ```
+    pub fn until_bestmove(mut self) -> impl Iterator<Item = EngineMessage> + 'a {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let msg = self.next()?;
+            done = msg.is_bestmove();
+            Some(msg)
+        })
+    }
+
+    /// Merge `info` messages that an engine split over several lines.
+    ///
+    /// A few engines send one logical update as several `info` lines (`info depth 10`
+    /// followed by `info pv 7g7f`). This adaptor merges each run of consecutive `info`
+    /// messages into one, starting a new record at every `info` line which contains a
+    /// `depth`. Any other message, like `bestmove`, also ends the record. Note that this
+    /// heuristic also merges an `info string` into the preceding record.
+    ///
+    /// Only use this for engines which are known to split their `info` output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let input = "info depth 1\ninfo pv 7g7f\ninfo depth 2\nbestmove 7g7f\n";
+    /// let msgs: Vec<EngineMessage> = EngineMessageStream::new(input).coalesce_info().collect();
+    /// assert_eq!(msgs.len(), 3);
+    /// assert_eq!(msgs[0].to_string(), "info depth 1 pv 7g7f");
+    /// ```
+    pub fn coalesce_info(self) -> impl Iterator<Item = EngineMessage> + 'a {
+        let starts_record = |params: &[InfoParam]| {
+            params
+                .iter()
+                .any(|param| matches!(param, InfoParam::Depth(_)))
+        };
+        let mut stream = self.peekable();
+        std::iter::from_fn(move || {
+            let msg = stream.next()?;
+            let EngineMessage::Info(mut params) = msg else {
+                return Some(msg);
+            };
+            while let Some(EngineMessage::Info(more)) = stream
+                .next_if(|next| matches!(next, EngineMessage::Info(more) if !starts_record(more)))
+            {
+                params.extend(more);
+            }
+            Some(EngineMessage::Info(params))
+        })
+    }
+
+    /// Iterate over the messages of the given kinds only.
+    ///
+    /// The kinds are the names returned by [`EngineMessage::kind`], such as `"info"`
+    /// or `"bestmove"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let input = "readyok\ninfo depth 1\nid name Foo\nbestmove 7g7f\n";
+    /// let msgs: Vec<EngineMessage> = EngineMessageStream::new(input)
+    ///     .filter_kinds(&["info", "bestmove"])
+    ///     .collect();
+    /// assert_eq!(msgs.len(), 2);
+    /// ```
+    pub fn filter_kinds(self, kinds: &[&'static str]) -> impl Iterator<Item = EngineMessage> + 'a {
+        let kinds = kinds.to_vec();
+        self.filter(move |msg| kinds.contains(&msg.kind()))
     }
 }
 
@@ -790,16 +1432,76 @@ impl Iterator for EngineMessageStream<'_> {
     type Item = EngineMessage;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(pair) = self.pairs.by_ref().next() {
-            let res = EngineMessage::inner_parse(pair);
-            return Some(res);
-        }
-        None
+        let pair = self
+            .pairs
+            .as_mut()?
+            .find(|pair| !pair.as_str().trim().is_empty())?;
+        Some(EngineMessage::inner_parse(pair, &ParseOptions::default()))
+    }
+}
+
+impl From<&str> for EngineMessage {
+    /// See [`EngineMessage::from_line`].
+    fn from(line: &str) -> Self {
+        Self::from_line(line)
     }
 }
 
 // HELPERS
 
+/// The input up to and including the terminator of the first non-blank line.
+fn first_line(input: &str) -> &str {
+    let mut start = 0;
+    while let Some(i) = input[start..].find(['\n', '\r']) {
+        let mut end = start + i + 1;
+        if input[start + i..].starts_with("\r\n") {
+            end += 1;
+        }
+        if !input[start..end].trim().is_empty() {
+            return &input[..end];
+        }
+        start = end;
+    }
+    input
+}
+
+/// Parse the first message of the input with `inner_parse`, and return it with the
+/// number of bytes consumed. This is the shared body of the `parse_prefix` functions.
+fn parse_prefix_with<T>(
+    input: &str,
+    inner_parse: fn(Pair<'_, Rule>, &ParseOptions) -> T,
+) -> Result<(T, usize), ParseError> {
+    let input = first_line(input);
+    match UsiParser::parse(Rule::start, input) {
+        Ok(mut pairs) => {
+            let pair = pairs.next().unwrap();
+            let consumed = consumed(input, pair.as_span().end());
+            Ok((inner_parse(pair, &ParseOptions::default()), consumed))
+        }
+        Err(err) => Err(ParseError::from_pest(err, input)),
+    }
+}
+
+/// The number of bytes consumed by a message ending at byte offset `end`, including
+/// trailing whitespace and the terminating newline (if not already part of the message).
+fn consumed(input: &str, end: usize) -> usize {
+    if input[..end].ends_with(['\n', '\r']) {
+        return end;
+    }
+    let rest = &input[end..];
+    let trimmed = rest.trim_start_matches([' ', '\t']);
+    let ws = rest.len() - trimmed.len();
+    let nl = if trimmed.starts_with("\r\n") {
+        2
+    } else if trimmed.starts_with(['\n', '\r']) {
+        1
+    } else {
+        // a junk prefix, followed by a message on the same line
+        return end;
+    };
+    end + ws + nl
+}
+
 // SAFETY: The PEST grammar ensures that all low-level parse/unwrap calls are safe.
 // Panics are justified since any panic would indicate a serious bug either in the
 // way this module hooks up the functions to the grammar or in the grammar itself.
@@ -813,46 +1515,68 @@ fn parse_move(pair: Pair<Rule>) -> Move {
     unreachable!()
 }
 
-fn parse_moves(pair: Pair<Rule>) -> Vec<Move> {
+/// Parse a list of moves, keeping at most `max_moves` moves.
+fn parse_moves(pair: Pair<Rule>, max_moves: usize) -> MoveList {
     let mut moves = Vec::<Move>::new();
 
     for sp in pair.into_inner() {
+        if moves.len() >= max_moves {
+            break;
+        }
         match sp.as_rule() {
             Rule::one_move => {
                 moves.push(as_move!(sp));
             }
             Rule::moves => {
-                let mvs: Vec<Move> = parse_moves(sp);
-                moves.extend(mvs);
+                moves.extend(parse_moves(sp, max_moves - moves.len()));
             }
             _ => unreachable!(),
         }
     }
 
-    moves
+    moves.into()
+}
+
+/// Integer types for [`parse_number`].
+trait Saturating: FromStr {
+    const MIN: Self;
+    const MAX: Self;
+}
+
+macro_rules! saturating {
+    ($($t:ty),*) => {
+        $(impl Saturating for $t {
+            const MIN: Self = <$t>::MIN;
+            const MAX: Self = <$t>::MAX;
+        })*
+    };
+}
+
+saturating!(u16, u32, u64, i32);
+
+/// Parse a number matched by the grammar, clamping it to the range of `T`.
+///
+/// The grammar only matches (signed) digits, so the only way parsing can fail is
+/// overflow. Peers may send absurd values like `info depth 70000`, which should not
+/// crash the parser.
+fn parse_number<T: Saturating>(s: &str) -> T {
+    s.parse::<T>()
+        .unwrap_or(if s.starts_with('-') { T::MIN } else { T::MAX })
 }
 
-fn parse_digits<T>(pair: Pair<Rule>) -> T
-where
-    T: FromStr,
-    T::Err: Debug,
-{
+fn parse_digits<T: Saturating>(pair: Pair<Rule>) -> T {
     for sp in pair.into_inner() {
         if let Rule::digits = sp.as_rule() {
-            return as_str!(sp).parse::<T>().unwrap();
+            return parse_number(as_str!(sp));
         }
     }
     unreachable!()
 }
 
-fn parse_integer<T>(pair: Pair<Rule>) -> T
-where
-    T: FromStr,
-    T::Err: Debug,
-{
+fn parse_integer<T: Saturating>(pair: Pair<Rule>) -> T {
     for sp in pair.into_inner() {
         if let Rule::integer = sp.as_rule() {
-            return as_str!(sp).parse::<T>().unwrap();
+            return parse_number(as_str!(sp));
         }
     }
     unreachable!()
@@ -861,11 +1585,11 @@ where
 fn parse_millisecs(pair: Pair<Rule>) -> Duration {
     for sp in pair.into_inner() {
         if let Rule::millisecs = sp.as_rule() {
-            let milliseconds: u64 = as_str!(sp).parse::<u64>().unwrap();
+            let milliseconds: u64 = parse_number(as_str!(sp));
             return Duration::from_millis(milliseconds);
         }
         if let Rule::digits = sp.as_rule() {
-            let milliseconds: u64 = as_str!(sp).parse::<u64>().unwrap();
+            let milliseconds: u64 = parse_number(as_str!(sp));
             return Duration::from_millis(milliseconds);
         }
     }
@@ -0,0 +1,81 @@
+//! This module implements tracking of the engine's ponder move.
+//!
+//! After `bestmove X ponder Y` a GUI that uses pondering sends the position after
+//! `X Y` followed by `go ponder`. If the opponent then actually plays `Y`, the GUI
+//! sends `ponderhit`, otherwise it sends `stop` and starts a new search. The
+//! [`PonderState`] records the prediction and checks these conditions.
+use crate::engine::{BestMoveParams, EngineMessage};
+use crate::gui::GuiMessage;
+use haitaka_types::Move;
+
+/// The ponder prediction of the last `bestmove`.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut state = PonderState::new();
+/// state.observe(&EngineMessage::parse("bestmove 7g7f ponder 3c3d\n").unwrap());
+/// let position = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+/// assert!(state.matches_position(&position));
+/// assert!(state.is_ponderhit(&"3c3d".parse().unwrap()));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PonderState {
+    prediction: Option<(Move, Move)>,
+}
+
+impl PonderState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the prediction of a `bestmove ... ponder ...` message.
+    ///
+    /// A `bestmove` without `ponder` clears the prediction. Other messages are ignored.
+    pub fn observe(&mut self, msg: &EngineMessage) {
+        if let EngineMessage::BestMove(params) = msg {
+            self.prediction = match params {
+                BestMoveParams::BestMove {
+                    bestmove,
+                    ponder: Some(ponder),
+                } => Some((*bestmove, *ponder)),
+                _ => None,
+            };
+        }
+    }
+
+    /// The engine's own move of the last `bestmove`, if it came with a ponder move.
+    pub fn bestmove(&self) -> Option<Move> {
+        self.prediction.map(|(bestmove, _)| bestmove)
+    }
+
+    /// The predicted reply of the opponent.
+    pub fn ponder(&self) -> Option<Move> {
+        self.prediction.map(|(_, ponder)| ponder)
+    }
+
+    /// Is this the position to ponder on?
+    ///
+    /// This is true if the moves of the `position` message end with the best move
+    /// followed by the ponder move.
+    pub fn matches_position(&self, position: &GuiMessage) -> bool {
+        let (Some((bestmove, ponder)), GuiMessage::Position { moves, .. }) =
+            (self.prediction, position)
+        else {
+            return false;
+        };
+        moves
+            .as_deref()
+            .is_some_and(|moves| moves.ends_with(&[bestmove, ponder]))
+    }
+
+    /// Did the opponent play the predicted move? If so, the GUI should send `ponderhit`.
+    pub fn is_ponderhit(&self, mv: &Move) -> bool {
+        self.ponder() == Some(*mv)
+    }
+
+    pub fn clear(&mut self) {
+        self.prediction = None;
+    }
+}
@@ -0,0 +1,127 @@
+//! This module implements a line-by-line reader for engine output.
+//!
+//! An [`EngineMessageReader`] reads one protocol line at a time from a [`BufRead`]
+//! (typically the engine's stdout) and parses it. Since it never reads ahead beyond
+//! the current line, it fits well into a select/poll loop.
+use crate::engine::EngineMessage;
+use std::io::{self, BufRead};
+
+/// The default maximum length of a line, in bytes, see [`EngineMessageReader::max_line_len`].
+pub const DEFAULT_MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Reads and parses engine messages one line at a time.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut reader = EngineMessageReader::new("usiok\nreadyok\n".as_bytes());
+/// assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+/// assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::ReadyOk));
+/// assert_eq!(reader.read_message().unwrap(), None);
+/// ```
+#[derive(Debug)]
+pub struct EngineMessageReader<R> {
+    reader: R,
+    max_line_len: usize,
+    buf: Vec<u8>,
+    /// The last line ended in `\r`, so a `\n` at the start of the next one completes it
+    skip_lf: bool,
+}
+
+impl<R: BufRead> EngineMessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            max_line_len: DEFAULT_MAX_LINE_LEN,
+            buf: Vec::new(),
+            skip_lf: false,
+        }
+    }
+
+    /// Set the maximum length of a line in bytes, not counting the line terminator.
+    ///
+    /// This guards against a runaway engine that never sends a newline.
+    #[must_use]
+    pub fn max_line_len(mut self, max_line_len: usize) -> Self {
+        self.max_line_len = max_line_len;
+        self
+    }
+
+    /// Read the next line and parse it.
+    ///
+    /// Lines may be terminated by `\n`, `\r\n` or `\r` (see [`crate::LineEnding`]).
+    /// Blank lines are skipped. Returns `Ok(None)` at the end of the input. A last
+    /// line without line terminator is parsed as if it was terminated.
+    ///
+    /// Input that does not conform to the protocol is returned as `Unknown`. An error
+    /// of kind [`io::ErrorKind::InvalidData`] is returned if a line is longer than the
+    /// maximum line length, or if it is not valid UTF-8. After a line that is too long,
+    /// the reader is left in the middle of that line.
+    pub fn read_message(&mut self) -> io::Result<Option<EngineMessage>> {
+        loop {
+            if !self.read_line()? {
+                return Ok(None);
+            }
+            let content = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            if content.len() > self.max_line_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line longer than {} bytes", self.max_line_len),
+                ));
+            }
+            let line = std::str::from_utf8(&self.buf)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if !line.trim().is_empty() {
+                return Ok(Some(EngineMessage::from_line(line)));
+            }
+        }
+    }
+
+    /// Read the next line, including its terminator, into `buf`.
+    ///
+    /// This is like [`BufRead::read_until`], but a line also ends at a `\r`. The `\n` of
+    /// a `\r\n` is skipped at the start of the next line, so that a line is returned
+    /// as soon as its `\r` arrives, without waiting for more input. At most
+    /// `max_line_len + 2` bytes are read. Returns `false` at the end of the input.
+    fn read_line(&mut self) -> io::Result<bool> {
+        self.buf.clear();
+        let limit = self.max_line_len.saturating_add(2);
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if available.is_empty() {
+                return Ok(!self.buf.is_empty());
+            }
+            if self.skip_lf {
+                self.skip_lf = false;
+                if available[0] == b'\n' {
+                    self.reader.consume(1);
+                    continue;
+                }
+            }
+            let available = &available[..available.len().min(limit - self.buf.len())];
+            let (n, done) = match available.iter().position(|&b| b == b'\n' || b == b'\r') {
+                Some(i) => {
+                    self.skip_lf = available[i] == b'\r';
+                    (i + 1, true)
+                }
+                None => (available.len(), false),
+            };
+            self.buf.extend_from_slice(&available[..n]);
+            self.reader.consume(n);
+            if done || self.buf.len() >= limit {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Consume the reader and return the underlying input.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
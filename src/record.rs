@@ -0,0 +1,122 @@
+//! This module implements [`GameRecord`], which reconstructs a game from the messages
+//! exchanged with an engine, for instance from a proxy log.
+use crate::engine::{BestMoveParams, EngineMessage};
+use crate::gui::{GameStatus, GoMode, GuiMessage};
+use crate::moves::MoveList;
+use crate::usi::UsiMessage;
+use haitaka_types::Move;
+
+/// The move history and result of a game.
+///
+/// Each `position` message from the GUI carries the complete history, so it replaces
+/// the moves recorded so far. The engine's `bestmove` is appended to the moves.
+/// `gameover` records the result, and `usinewgame` starts a new record.
+///
+/// While the engine ponders (`go ponder`), the last move of the position is the
+/// predicted reply, which has not been played. It is held back until `ponderhit`
+/// confirms it. On a ponder miss, the GUI sends `stop` and discards the engine's
+/// `bestmove`, so the record drops both the `bestmove` and the predicted reply.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let messages = [
+///     UsiMessage::UsiGuiToEngine(GuiMessage::parse("position startpos moves 7g7f\n").unwrap()),
+///     UsiMessage::UsiEngineToGui(EngineMessage::parse("bestmove 3c3d\n").unwrap()),
+/// ];
+/// let record = GameRecord::from_messages(&messages);
+/// assert_eq!(record.moves().to_string(), "7g7f 3c3d");
+/// assert_eq!(record.result(), None);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GameRecord {
+    sfen: Option<String>,
+    moves: MoveList,
+    result: Option<GameStatus>,
+    /// The engine was sent `go ponder`, and no `ponderhit` followed yet
+    pondering: bool,
+    /// The predicted reply held back while pondering
+    ponder_move: Option<Move>,
+}
+
+impl GameRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold a sequence of messages into a record.
+    pub fn from_messages(messages: &[UsiMessage]) -> Self {
+        let mut record = Self::new();
+        for msg in messages {
+            record.update(msg);
+        }
+        record
+    }
+
+    /// Fold one message into the record.
+    pub fn update(&mut self, msg: &UsiMessage) {
+        match msg {
+            UsiMessage::UsiGuiToEngine(GuiMessage::UsiNewGame) => *self = Self::new(),
+            UsiMessage::UsiGuiToEngine(GuiMessage::Position { sfen, moves }) => {
+                self.sfen = sfen.clone();
+                self.moves = moves.clone().unwrap_or_default();
+                self.pondering = false;
+                self.ponder_move = None;
+            }
+            UsiMessage::UsiGuiToEngine(GuiMessage::Go(params))
+                if matches!(params.interpret(), GoMode::Ponder) =>
+            {
+                let mut moves = std::mem::take(&mut self.moves).into_vec();
+                self.ponder_move = moves.pop();
+                self.moves = moves.into();
+                self.pondering = true;
+            }
+            UsiMessage::UsiGuiToEngine(GuiMessage::PonderHit) if self.pondering => {
+                if let Some(mv) = self.ponder_move.take() {
+                    self.moves.push(mv);
+                }
+                self.pondering = false;
+            }
+            UsiMessage::UsiGuiToEngine(GuiMessage::GameOver(status)) => self.result = Some(*status),
+            // the answer to a `go ponder` without `ponderhit`, which the GUI discards
+            UsiMessage::UsiEngineToGui(EngineMessage::BestMove(_)) if self.pondering => {
+                self.pondering = false;
+                self.ponder_move = None;
+            }
+            UsiMessage::UsiEngineToGui(EngineMessage::BestMove(BestMoveParams::BestMove {
+                bestmove,
+                ..
+            })) => self.moves.push(*bestmove),
+            _ => {}
+        }
+    }
+
+    /// The SFEN of the start position, or `None` for `startpos`.
+    pub fn sfen(&self) -> Option<&str> {
+        self.sfen.as_deref()
+    }
+
+    /// The moves played. While the engine ponders, this does not include the predicted
+    /// reply.
+    pub fn moves(&self) -> &MoveList {
+        &self.moves
+    }
+
+    /// The result from `gameover`, from the engine's point of view.
+    pub fn result(&self) -> Option<GameStatus> {
+        self.result
+    }
+
+    /// The `position` message for the current state of the game.
+    pub fn position(&self) -> GuiMessage {
+        GuiMessage::Position {
+            sfen: self.sfen.clone(),
+            moves: if self.moves.is_empty() {
+                None
+            } else {
+                Some(self.moves.clone())
+            },
+        }
+    }
+}
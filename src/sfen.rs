@@ -0,0 +1,800 @@
+//! This module contains a minimal SFEN board model.
+//!
+//! The board is only meant to apply USI moves to a position and to serialize the
+//! resulting position back to SFEN. It is not a move generator. Moves are checked
+//! for pseudo-legality only: the moving piece must belong to the side to move and
+//! must be able to reach the target square, captures of own pieces are rejected,
+//! promotions must be possible, and drops must be from hand onto an empty square
+//! (pawn drops also follow the nifu rule). Whether a move leaves the own king in
+//! check, or whether a pawn drop gives mate, is not verified.
+//!
+//! For a real board implementation, see the [haitaka](https://crates.io/crates/haitaka) crate.
+use crate::engine::InfoParam;
+use crate::gui::{GuiMessage, SFEN_STARTPOS};
+use crate::parser::ParseError;
+use haitaka_types::{Color, ColoredPiece, File, Move, Piece, Rank, Square};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Errors that can occur when applying moves to a position.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PositionError {
+    /// The SFEN string is malformed.
+    InvalidSfen(String),
+
+    /// The move at this index of the move list is not legal in the position.
+    IllegalMove { index: usize, mv: Move },
+
+    /// The message is not a `position` message.
+    NotAPosition,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidSfen(sfen) => write!(f, "invalid SFEN \"{}\"", sfen),
+            Self::IllegalMove { index, mv } => write!(f, "illegal move {} at index {}", mv, index),
+            Self::NotAPosition => write!(f, "not a position message"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// A structurally valid SFEN string.
+///
+/// A `Sfen` can only be constructed from a string which describes a complete position:
+/// a board of 9 ranks of 9 files, the side to move, the pieces in hand, and optionally
+/// the move number. A hand may not hold more pieces of a kind than a full set has
+/// (18 pawns, 4 lances, knights, silvers or golds, 2 bishops or rooks). The string
+/// itself is kept as given (apart from trimming).
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let sfen: Sfen = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1".parse().unwrap();
+/// assert_eq!(sfen.as_str(), SFEN_STARTPOS);
+/// assert!("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b".parse::<Sfen>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sfen(String);
+
+impl Sfen {
+    /// Validate a SFEN string. The string "startpos" is accepted as an alias for
+    /// [`SFEN_STARTPOS`].
+    pub fn new(sfen: &str) -> Result<Self, PositionError> {
+        let sfen = sfen.trim();
+        Board::from_sfen(sfen)?;
+        if sfen == "startpos" {
+            Ok(Self::startpos())
+        } else {
+            Ok(Self(sfen.to_string()))
+        }
+    }
+
+    /// The standard start position.
+    pub fn startpos() -> Self {
+        Self(SFEN_STARTPOS.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Render the position as an ASCII board, for debugging.
+    ///
+    /// Pieces are shown by their SFEN letters, uppercase for Black and lowercase for
+    /// White, and empty squares as `.`. The pieces in hand of White are shown above
+    /// the board, those of Black below it.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let sfen = Sfen::new("4k4/9/9/9/9/9/9/9/4K4 b 2Pr 1").unwrap();
+    /// let board = sfen.to_ascii_board();
+    /// assert!(board.starts_with("White hand: r\n"));
+    /// assert!(board.contains("  .  .  .  .  K  .  .  .  .  i\n"));
+    /// assert!(board.ends_with("Black hand: 2P\nBlack to move, move 1\n"));
+    /// ```
+    pub fn to_ascii_board(&self) -> String {
+        // a Sfen is validated on construction
+        Board::from_sfen(&self.0).unwrap().to_ascii()
+    }
+}
+
+impl FromStr for Sfen {
+    type Err = PositionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl AsRef<str> for Sfen {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Sfen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The pieces that can be held in hand, in SFEN order.
+const HAND_PIECES: [Piece; 7] = [
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Gold,
+    Piece::Silver,
+    Piece::Knight,
+    Piece::Lance,
+    Piece::Pawn,
+];
+
+/// The number of pieces of each kind in [`HAND_PIECES`] in a full set.
+const HAND_LIMITS: [u8; 7] = [2, 2, 4, 4, 4, 4, 18];
+
+/// A minimal board: pieces on squares, pieces in hand, side to move and move number.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Board {
+    squares: [Option<ColoredPiece>; Square::NUM],
+    hands: [[u8; 7]; Color::NUM],
+    side_to_move: Color,
+    move_number: u32,
+}
+
+impl Board {
+    /// Create a board from a SFEN string. The string "startpos" is accepted as
+    /// an alias for [`SFEN_STARTPOS`]. The move number is optional.
+    pub(crate) fn from_sfen(sfen: &str) -> Result<Self, PositionError> {
+        let sfen = sfen.trim();
+        let sfen = if sfen == "startpos" {
+            SFEN_STARTPOS
+        } else {
+            sfen
+        };
+        let invalid = || PositionError::InvalidSfen(sfen.to_string());
+
+        let fields: Vec<&str> = sfen.split_whitespace().collect();
+        if fields.len() != 3 && fields.len() != 4 {
+            return Err(invalid());
+        }
+
+        let mut board = Board {
+            squares: [None; Square::NUM],
+            hands: [[0; 7]; Color::NUM],
+            side_to_move: Color::Black,
+            move_number: 1,
+        };
+
+        // board
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != Rank::NUM {
+            return Err(invalid());
+        }
+        for (r, rank) in ranks.iter().enumerate() {
+            // files are listed from left (file 9) to right (file 1)
+            let mut f = File::NUM;
+            let mut chars = rank.chars();
+            while let Some(c) = chars.next() {
+                if let Some(n) = c.to_digit(10) {
+                    let n = n as usize;
+                    if n == 0 || n > f {
+                        return Err(invalid());
+                    }
+                    f -= n;
+                    continue;
+                }
+                let token = if c == '+' {
+                    format!("+{}", chars.next().ok_or_else(invalid)?)
+                } else {
+                    c.to_string()
+                };
+                let (piece, color) = Piece::try_from_str(&token).ok_or_else(invalid)?;
+                if f == 0 {
+                    return Err(invalid());
+                }
+                f -= 1;
+                board.squares[square(f, r).to_index()] = Some(ColoredPiece { piece, color });
+            }
+            if f != 0 {
+                return Err(invalid());
+            }
+        }
+
+        // side to move
+        board.side_to_move = match fields[1] {
+            "b" => Color::Black,
+            "w" => Color::White,
+            _ => return Err(invalid()),
+        };
+
+        // hands
+        if fields[2] != "-" {
+            let mut count: Option<u8> = None;
+            for c in fields[2].chars() {
+                if let Some(d) = c.to_digit(10) {
+                    let n = count.unwrap_or(0).checked_mul(10).ok_or_else(invalid)?;
+                    count = Some(n.checked_add(d as u8).ok_or_else(invalid)?);
+                    continue;
+                }
+                let (piece, color) = Piece::try_from_char(c).ok_or_else(invalid)?;
+                let h = hand_index(piece).ok_or_else(invalid)?;
+                let held = &mut board.hands[color.to_index()][h];
+                *held = held
+                    .checked_add(count.take().unwrap_or(1))
+                    .filter(|&n| n <= HAND_LIMITS[h])
+                    .ok_or_else(invalid)?;
+            }
+            if count.is_some() {
+                return Err(invalid());
+            }
+        }
+
+        // move number
+        if let Some(n) = fields.get(3) {
+            board.move_number = n.parse::<u32>().map_err(|_| invalid())?;
+        }
+
+        Ok(board)
+    }
+
+    /// The side to move.
+    pub(crate) fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// Serialize the board to a SFEN string.
+    pub(crate) fn to_sfen(&self) -> String {
+        let mut ranks: Vec<String> = Vec::with_capacity(Rank::NUM);
+        for r in 0..Rank::NUM {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for f in (0..File::NUM).rev() {
+                match self.squares[square(f, r).to_index()] {
+                    Some(cp) => {
+                        if empty > 0 {
+                            rank += &empty.to_string();
+                            empty = 0;
+                        }
+                        rank += &cp.to_string();
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank += &empty.to_string();
+            }
+            ranks.push(rank);
+        }
+
+        let mut hands = String::new();
+        for color in [Color::Black, Color::White] {
+            for (h, piece) in HAND_PIECES.iter().enumerate() {
+                let n = self.hands[color.to_index()][h];
+                if n > 1 {
+                    hands += &n.to_string();
+                }
+                if n > 0 {
+                    hands += &piece.to_str(color);
+                }
+            }
+        }
+        if hands.is_empty() {
+            hands += "-";
+        }
+
+        let color = match self.side_to_move {
+            Color::Black => "b",
+            Color::White => "w",
+        };
+
+        format!(
+            "{} {} {} {}",
+            ranks.join("/"),
+            color,
+            hands,
+            self.move_number
+        )
+    }
+
+    /// Render the board as a 9x9 grid with file numbers and rank letters, framed
+    /// by the pieces in hand of both sides and followed by the side to move.
+    pub(crate) fn to_ascii(&self) -> String {
+        let mut out = String::new();
+        out += &format!("White hand: {}\n", self.hand_str(Color::White));
+        for f in (1..=File::NUM).rev() {
+            out += &format!("{:>3}", f);
+        }
+        out += "\n";
+        for r in 0..Rank::NUM {
+            for f in (0..File::NUM).rev() {
+                let cell = self.squares[square(f, r).to_index()]
+                    .map_or_else(|| ".".to_string(), |cp| cp.to_string());
+                out += &format!("{:>3}", cell);
+            }
+            out += &format!("  {}\n", (b'a' + r as u8) as char);
+        }
+        out += &format!("Black hand: {}\n", self.hand_str(Color::Black));
+        let side = match self.side_to_move {
+            Color::Black => "Black",
+            Color::White => "White",
+        };
+        out += &format!("{} to move, move {}\n", side, self.move_number);
+        out
+    }
+
+    /// The pieces in hand of `color`, such as "R 2P", or "-" if there are none.
+    fn hand_str(&self, color: Color) -> String {
+        let mut pieces = Vec::new();
+        for (h, piece) in HAND_PIECES.iter().enumerate() {
+            match self.hands[color.to_index()][h] {
+                0 => {}
+                1 => pieces.push(piece.to_str(color).to_string()),
+                n => pieces.push(format!("{}{}", n, piece.to_str(color))),
+            }
+        }
+        if pieces.is_empty() {
+            "-".to_string()
+        } else {
+            pieces.join(" ")
+        }
+    }
+
+    /// Apply a move for the side to move.
+    ///
+    /// Returns false, leaving the board unchanged, if the move is not pseudo-legal.
+    pub(crate) fn play(&mut self, mv: &Move) -> bool {
+        let color = self.side_to_move;
+        match *mv {
+            Move::Drop { piece, to } => {
+                let Some(h) = hand_index(piece) else {
+                    return false;
+                };
+                if self.hands[color.to_index()][h] == 0
+                    || self.squares[to.to_index()].is_some()
+                    || !piece.can_drop(color, to)
+                {
+                    return false;
+                }
+                if piece == Piece::Pawn {
+                    let f = to.file().to_index();
+                    let nifu = (0..Rank::NUM).any(|r| {
+                        self.squares[square(f, r).to_index()]
+                            == Some(ColoredPiece {
+                                piece: Piece::Pawn,
+                                color,
+                            })
+                    });
+                    if nifu {
+                        return false;
+                    }
+                }
+                self.hands[color.to_index()][h] -= 1;
+                self.squares[to.to_index()] = Some(ColoredPiece { piece, color });
+            }
+            Move::BoardMove {
+                from,
+                to,
+                promotion,
+            } => {
+                let Some(cp) = self.squares[from.to_index()] else {
+                    return false;
+                };
+                if cp.color != color || !self.reaches(cp.piece, color, from, to) {
+                    return false;
+                }
+                let captured = self.squares[to.to_index()];
+                if let Some(target) = captured
+                    && (target.color == color || target.piece == Piece::King)
+                {
+                    return false;
+                }
+                let piece = if promotion {
+                    if !cp.piece.can_promote(color, from) && !cp.piece.can_promote(color, to) {
+                        return false;
+                    }
+                    cp.piece.promote()
+                } else {
+                    if cp.piece.must_promote(color, to) {
+                        return false;
+                    }
+                    cp.piece
+                };
+                if let Some(h) = captured.and_then(|target| hand_index(target.piece.unpromote())) {
+                    self.hands[color.to_index()][h] += 1;
+                }
+                self.squares[from.to_index()] = None;
+                self.squares[to.to_index()] = Some(ColoredPiece { piece, color });
+            }
+        }
+        self.side_to_move = !color;
+        // any u32 is a valid move number, so the last one just sticks
+        self.move_number = self.move_number.saturating_add(1);
+        true
+    }
+
+    /// Can the piece move from `from` to `to` given the current occupancy?
+    fn reaches(&self, piece: Piece, color: Color, from: Square, to: Square) -> bool {
+        let f0 = from.file().to_index() as i32;
+        let r0 = from.rank().to_index() as i32;
+        let df = to.file().to_index() as i32 - f0;
+        let dr = to.rank().to_index() as i32 - r0;
+
+        // normalize so that "forward" is always a negative rank delta
+        let fwd = match color {
+            Color::Black => dr,
+            Color::White => -dr,
+        };
+        let step = df.abs().max(dr.abs()) == 1;
+        let gold =
+            (fwd == -1 && df.abs() <= 1) || (fwd == 0 && df.abs() == 1) || (fwd == 1 && df == 0);
+        let diagonal = df != 0 && df.abs() == dr.abs();
+        let orthogonal = (df == 0) != (dr == 0);
+
+        // are all squares between from and to empty?
+        let clear = || {
+            let n = df.abs().max(dr.abs());
+            (1..n).all(|i| {
+                let f = (f0 + i * df.signum()) as usize;
+                let r = (r0 + i * dr.signum()) as usize;
+                self.squares[square(f, r).to_index()].is_none()
+            })
+        };
+
+        match piece {
+            Piece::Pawn => df == 0 && fwd == -1,
+            Piece::Lance => df == 0 && fwd < 0 && clear(),
+            Piece::Knight => df.abs() == 1 && fwd == -2,
+            Piece::Silver => (fwd == -1 && df.abs() <= 1) || (fwd == 1 && df.abs() == 1),
+            Piece::Gold | Piece::Tokin | Piece::PLance | Piece::PKnight | Piece::PSilver => gold,
+            Piece::King => step,
+            Piece::Bishop => diagonal && clear(),
+            Piece::Rook => orthogonal && clear(),
+            Piece::PBishop => step || (diagonal && clear()),
+            Piece::PRook => step || (orthogonal && clear()),
+        }
+    }
+}
+
+/// Index of a piece in the hand arrays, if it's a piece that can be held in hand.
+fn hand_index(piece: Piece) -> Option<usize> {
+    HAND_PIECES.iter().position(|&p| p == piece)
+}
+
+/// Square from file index (0 is file 1) and rank index (0 is rank a).
+fn square(f: usize, r: usize) -> Square {
+    Square::new(File::index(f), Rank::index(r))
+}
+
+/// Apply the principal variation of an `info` message to a base position.
+///
+/// Returns the SFEN after each ply of the first `pv` param in `info`. The base
+/// position is given as a SFEN string, or as "startpos". If there is no `pv`,
+/// an empty vector is returned. If a move in the pv is not legal, an
+/// [`PositionError::IllegalMove`] error is returned for the first such move.
+/// See the [module documentation](self) for what "legal" means here.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let EngineMessage::Info(info) = EngineMessage::parse("info depth 2 pv 7g7f 3c3d\n").unwrap() else {
+///     panic!();
+/// };
+/// let sfens = pv_positions(&info, "startpos").unwrap();
+/// assert_eq!(sfens.len(), 2);
+/// assert_eq!(sfens[1], "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3");
+/// ```
+pub fn pv_positions(info: &[InfoParam], base_sfen: &str) -> Result<Vec<String>, PositionError> {
+    let Some(pv) = info.iter().find_map(|param| match param {
+        InfoParam::Pv(moves) => Some(moves),
+        _ => None,
+    }) else {
+        return Ok(Vec::new());
+    };
+
+    let mut board = Board::from_sfen(base_sfen)?;
+    pv.iter()
+        .enumerate()
+        .map(|(index, mv)| {
+            if board.play(mv) {
+                Ok(board.to_sfen())
+            } else {
+                Err(PositionError::IllegalMove { index, mv: *mv })
+            }
+        })
+        .collect()
+}
+
+impl GuiMessage {
+    /// The `position sfen ...` message for a SFEN string, which is validated first.
+    ///
+    /// See [`Sfen::new`] for the validation. A malformed SFEN is reported as
+    /// [`ParseError::InvalidSfen`].
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = GuiMessage::from_sfen("8l/9/9/9/9/9/9/9/K8 b - 1").unwrap();
+    /// assert_eq!(msg.to_string(), "position sfen 8l/9/9/9/9/9/9/9/K8 b - 1");
+    /// assert!(GuiMessage::from_sfen("8l/9 b - 1").is_err());
+    /// ```
+    pub fn from_sfen(sfen: &str) -> Result<Self, ParseError> {
+        let sfen = Sfen::new(sfen).map_err(|_| ParseError::InvalidSfen(sfen.trim().to_string()))?;
+        Ok(Self::Position {
+            sfen: Some(sfen.into_string()),
+            moves: None,
+        })
+    }
+
+    /// The side to move after the moves of a `position` message.
+    ///
+    /// This is derived from the side to move of the base position (`startpos` or SFEN)
+    /// and the parity of the move list. The moves themselves are not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// use haitaka_types::Color;
+    /// let msg = GuiMessage::parse("position startpos moves 7g7f\n").unwrap();
+    /// assert_eq!(msg.side_to_move(), Ok(Color::White));
+    /// ```
+    pub fn side_to_move(&self) -> Result<Color, PositionError> {
+        let GuiMessage::Position { sfen, moves } = self else {
+            return Err(PositionError::NotAPosition);
+        };
+        let base = match sfen {
+            Some(sfen) => Board::from_sfen(sfen)?.side_to_move(),
+            None => Color::Black,
+        };
+        let plies = moves.as_ref().map_or(0, |moves| moves.len());
+        Ok(if plies % 2 == 0 { base } else { !base })
+    }
+
+    /// The SFEN of the position after applying the moves of a `position` message.
+    ///
+    /// If a move is not legal, a [`PositionError::IllegalMove`] error is returned for
+    /// the first such move. See the [module documentation](self) for what "legal" means here.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = GuiMessage::parse("position startpos moves 7g7f\n").unwrap();
+    /// assert_eq!(
+    ///     msg.resulting_sfen().unwrap(),
+    ///     "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2"
+    /// );
+    /// ```
+    pub fn resulting_sfen(&self) -> Result<String, PositionError> {
+        let GuiMessage::Position { sfen, moves } = self else {
+            return Err(PositionError::NotAPosition);
+        };
+        let mut board = Board::from_sfen(sfen.as_deref().unwrap_or("startpos"))?;
+        for (index, mv) in moves.iter().flatten().enumerate() {
+            if !board.play(mv) {
+                return Err(PositionError::IllegalMove { index, mv: *mv });
+            }
+        }
+        Ok(board.to_sfen())
+    }
+
+    /// The SFEN after each move of a `position` message, paired with the move.
+    ///
+    /// This is the move-by-move version of [`GuiMessage::resulting_sfen`], handy for
+    /// tracking down position desyncs. The last SFEN is the resulting position. Errors
+    /// are returned as by `resulting_sfen`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+    /// let trace = msg.position_trace().unwrap();
+    /// assert_eq!(trace.len(), 2);
+    /// assert_eq!(trace[0].0.to_string(), "7g7f");
+    /// assert_eq!(trace[1].1, msg.resulting_sfen().unwrap());
+    /// ```
+    pub fn position_trace(&self) -> Result<Vec<(Move, String)>, PositionError> {
+        let GuiMessage::Position { sfen, moves } = self else {
+            return Err(PositionError::NotAPosition);
+        };
+        let mut board = Board::from_sfen(sfen.as_deref().unwrap_or("startpos"))?;
+        moves
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, mv)| {
+                if board.play(mv) {
+                    Ok((*mv, board.to_sfen()))
+                } else {
+                    Err(PositionError::IllegalMove { index, mv: *mv })
+                }
+            })
+            .collect()
+    }
+
+    /// Do two `position` messages describe the same position?
+    ///
+    /// Both messages are normalized to the SFEN after their moves, so `startpos moves ...`
+    /// compares equal to the equivalent explicit SFEN. The move number is ignored: only
+    /// the board, the side to move and the pieces in hand are compared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let a = GuiMessage::parse("position startpos\n").unwrap();
+    /// let b = GuiMessage::parse(format!("position sfen {}\n", SFEN_STARTPOS)).unwrap();
+    /// assert_eq!(a.position_eq(&b), Ok(true));
+    /// ```
+    pub fn position_eq(&self, other: &Self) -> Result<bool, PositionError> {
+        let a = self.resulting_sfen()?;
+        let b = other.resulting_sfen()?;
+        Ok(without_move_number(&a) == without_move_number(&b))
+    }
+}
+
+fn without_move_number(sfen: &str) -> &str {
+    sfen.rsplit_once(' ').map_or(sfen, |(head, _)| head)
+}
+
+/// The default capacity of a [`PositionCache`].
+pub const DEFAULT_POSITION_CACHE_CAPACITY: usize = 4096;
+
+/// Memoizes [`GuiMessage::resulting_sfen`] by move prefix.
+///
+/// In self-play the same `position startpos moves ...` prefixes are applied over and
+/// over: each position of a game extends the previous one by a move or two, and games
+/// often share their openings. The cache stores the board after the moves of each
+/// `position` message it has seen. A later message whose moves extend a cached move
+/// list only needs to apply the remaining moves.
+///
+/// Entries are keyed by a hash of the base position and the move prefix, which is
+/// computed incrementally, so a lookup hashes the moves once and only compares the
+/// moves of the longest matching entry. Each entry keeps a copy of its move list and
+/// a board (a few hundred bytes), so the memory used grows with the number of entries
+/// times the game length. The number of entries is capped by the capacity. When the
+/// cache is full, the oldest entry is evicted.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut cache = PositionCache::new();
+/// let msg = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+/// assert_eq!(cache.resulting_sfen(&msg), msg.resulting_sfen());
+/// assert_eq!(cache.hits(), 0);
+/// let msg = GuiMessage::parse("position startpos moves 7g7f 3c3d 2g2f\n").unwrap();
+/// assert_eq!(cache.resulting_sfen(&msg), msg.resulting_sfen());
+/// assert_eq!(cache.hits(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PositionCache {
+    capacity: usize,
+    hits: u64,
+    entries: HashMap<u64, CacheEntry>,
+    /// The keys of `entries`, oldest first
+    order: VecDeque<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+    base: String,
+    moves: Vec<Move>,
+    board: Board,
+}
+
+impl Default for PositionCache {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_POSITION_CACHE_CAPACITY)
+    }
+}
+
+impl PositionCache {
+    /// Create a cache with [`DEFAULT_POSITION_CACHE_CAPACITY`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a cache holding at most `capacity` positions.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            hits: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The same as [`GuiMessage::resulting_sfen`], reusing the longest cached move prefix.
+    pub fn resulting_sfen(&mut self, msg: &GuiMessage) -> Result<String, PositionError> {
+        let GuiMessage::Position { sfen, moves } = msg else {
+            return Err(PositionError::NotAPosition);
+        };
+        let base = sfen.as_deref().unwrap_or("startpos").trim();
+        let moves: &[Move] = moves.as_deref().unwrap_or(&[]);
+
+        // keys[n] is the key of the first n moves
+        let mut hasher = DefaultHasher::new();
+        base.hash(&mut hasher);
+        let mut keys = Vec::with_capacity(moves.len() + 1);
+        keys.push(hasher.finish());
+        for mv in moves {
+            mv.hash(&mut hasher);
+            keys.push(hasher.finish());
+        }
+
+        let cached = (1..=moves.len()).rev().find_map(|n| {
+            let entry = self.entries.get(&keys[n])?;
+            (entry.base == base && entry.moves == moves[..n]).then(|| (n, entry.board.clone()))
+        });
+        let (start, mut board) = match cached {
+            Some(cached) => {
+                self.hits += 1;
+                cached
+            }
+            None => (0, Board::from_sfen(base)?),
+        };
+        for (index, mv) in moves.iter().enumerate().skip(start) {
+            if !board.play(mv) {
+                return Err(PositionError::IllegalMove { index, mv: *mv });
+            }
+        }
+        let sfen = board.to_sfen();
+        if start < moves.len() {
+            self.insert(keys[moves.len()], base, moves, board);
+        }
+        Ok(sfen)
+    }
+
+    fn insert(&mut self, key: u64, base: &str, moves: &[Move], board: Board) {
+        if self.capacity == 0 {
+            return;
+        }
+        let entry = CacheEntry {
+            base: base.to_string(),
+            moves: moves.to_vec(),
+            board,
+        };
+        // on a (very unlikely) hash collision, the new entry replaces the old one
+        if self.entries.insert(key, entry).is_none() {
+            self.order.push_back(key);
+            if self.order.len() > self.capacity
+                && let Some(oldest) = self.order.pop_front()
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// The number of lookups that reused a cached position.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of cached positions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all cached positions. The hit count is kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
@@ -1,7 +1,9 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::*;
-    use haitaka_types::{Move, Square};
+    use haitaka_types::{Color, Move, Square};
+    use std::borrow::Cow;
     use std::time::Duration;
 
     fn s(s: &str) -> String {
@@ -18,6 +20,42 @@ mod tests {
         assert_eq!(msg, GuiMessage::Usi);
     }
 
+    #[test]
+    fn test_gui_gameover_without_result() {
+        for input in ["gameover\n", "gameover unknown\n", "gameover  \n"] {
+            let msg = GuiMessage::parse(input).unwrap();
+            assert_eq!(msg, GuiMessage::Unknown(s(input.trim_end_matches('\n'))));
+            let msgs: Vec<GuiMessage> = GuiMessageStream::new(input).collect();
+            assert!(msgs.iter().all(|msg| matches!(msg, GuiMessage::Unknown(_))));
+        }
+        let msg = GuiMessage::parse("gameover draw\n").unwrap();
+        assert_eq!(msg, GuiMessage::GameOver(GameStatus::Draw));
+    }
+
+    #[test]
+    fn test_gui_setoption_value_with_spaces() {
+        let input = "setoption name EvalFile value /path/with spaces/eval.bin\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::SetOption {
+                name: s("EvalFile"),
+                value: Some(s("/path/with spaces/eval.bin")),
+            }
+        );
+        assert_eq!(msg.to_usi_line(), input);
+
+        // inner whitespace is kept verbatim, outer whitespace is trimmed
+        let msg = GuiMessage::parse("setoption name BookFile value  my  book.bin \n").unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::SetOption {
+                name: s("BookFile"),
+                value: Some(s("my  book.bin")),
+            }
+        );
+    }
+
     #[test]
     fn test_gui_usinewgame() {
         let msg = GuiMessage::parse("usinewgame\n").unwrap();
@@ -92,6 +130,34 @@ mod tests {
         assert_eq!(format!("{msg}\n"), s);
     }
 
+    #[test]
+    fn test_gui_debug_argument() {
+        assert_eq!(
+            GuiMessage::parse("debug\n").unwrap(),
+            GuiMessage::Debug(true)
+        );
+        assert_eq!(
+            GuiMessage::parse("debug on\n").unwrap(),
+            GuiMessage::Debug(true)
+        );
+        assert_eq!(
+            GuiMessage::parse("debug off\n").unwrap(),
+            GuiMessage::Debug(false)
+        );
+        // anything other than on/off is not a debug command
+        for input in [
+            "debug xyz\n",
+            "debug maybe\n",
+            "debug onoff\n",
+            "debug on off\n",
+        ] {
+            assert_eq!(
+                GuiMessage::parse(input).unwrap(),
+                GuiMessage::Unknown(s(input))
+            );
+        }
+    }
+
     #[test]
     fn test_gui_roundtrip_debug_on() {
         let msg = GuiMessage::Debug(true);
@@ -127,6 +193,29 @@ mod tests {
         assert_eq!(format!("{msg}\n"), s);
     }
 
+    #[test]
+    fn test_gui_roundtrip_setoption_empty_value() {
+        let input = "setoption name LearningFile value <empty>\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::SetOption {
+                name: s("LearningFile"),
+                value: Some(s(""))
+            }
+        );
+        assert_eq!(format!("{msg}\n"), input);
+
+        let msg = GuiMessage::parse("setoption name LearningFile value actualtext\n").unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::SetOption {
+                name: s("LearningFile"),
+                value: Some(s("actualtext"))
+            }
+        );
+    }
+
     #[test]
     fn test_gui_roundtrip_setoption_without_value() {
         let msg = GuiMessage::SetOption {
@@ -197,17 +286,109 @@ mod tests {
     #[test]
     fn test_gui_roundtrip_position_startpos() {
         let sfen: Option<String> = None;
-        let moves: Option<Vec<Move>> = None;
+        let moves: Option<MoveList> = None;
         let msg = GuiMessage::Position { sfen, moves };
         let s = format!("{msg}\n");
         assert_eq!(s, "position startpos\n");
         assert_eq!(GuiMessage::parse(&s).unwrap(), msg);
     }
 
+    #[test]
+    fn test_gui_position_side_to_move() {
+        let side = |input: &str| GuiMessage::parse(input).unwrap().side_to_move();
+        assert_eq!(side("position startpos\n"), Ok(Color::Black));
+        assert_eq!(side("position startpos moves 7g7f\n"), Ok(Color::White));
+        assert_eq!(
+            side("position startpos moves 7g7f 3c3d\n"),
+            Ok(Color::Black)
+        );
+        assert_eq!(
+            side(
+                "position sfen 8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124\n"
+            ),
+            Ok(Color::White)
+        );
+        assert_eq!(
+            side(
+                "position sfen 8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124 moves 8c8d\n"
+            ),
+            Ok(Color::Black)
+        );
+        assert_eq!(side("isready\n"), Err(PositionError::NotAPosition));
+    }
+
+    #[test]
+    fn test_gui_position_sfen_fields() {
+        // board and side to move only
+        let msg = GuiMessage::parse(
+            "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b\n",
+        )
+        .unwrap();
+        assert!(msg.is_unknown());
+
+        // all four fields
+        let msg = GuiMessage::parse("position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 2 moves 3c3d\n")
+        .unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Position {
+                sfen: Some(s(
+                    "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 2"
+                )),
+                moves: Some("3c3d".parse().unwrap()),
+            }
+        );
+
+        // a rank with more than 9 files passes the grammar, but not the validation
+        let msg = GuiMessage::parse(
+            "position sfen lnsgkgsnl/1r5b1/ppppppppp/99/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1\n",
+        )
+        .unwrap();
+        assert!(msg.is_unknown());
+
+        assert_eq!(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b".parse::<Sfen>(),
+            Err(PositionError::InvalidSfen(s(
+                "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b"
+            )))
+        );
+        assert_eq!(Sfen::new("startpos").unwrap(), Sfen::startpos());
+        assert_eq!(Sfen::startpos().as_str(), SFEN_STARTPOS);
+    }
+
+    #[test]
+    fn test_sfen_ascii_board() {
+        let expected = "\
+White hand: -
+  9  8  7  6  5  4  3  2  1
+  l  n  s  g  k  g  s  n  l  a
+  .  r  .  .  .  .  .  b  .  b
+  p  p  p  p  p  p  p  p  p  c
+  .  .  .  .  .  .  .  .  .  d
+  .  .  .  .  .  .  .  .  .  e
+  .  .  .  .  .  .  .  .  .  f
+  P  P  P  P  P  P  P  P  P  g
+  .  B  .  .  .  .  .  R  .  h
+  L  N  S  G  K  G  S  N  L  i
+Black hand: -
+Black to move, move 1
+";
+        assert_eq!(Sfen::new(SFEN_STARTPOS).unwrap().to_ascii_board(), expected);
+
+        let sfen = Sfen::new(
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+        )
+        .unwrap();
+        let board = sfen.to_ascii_board();
+        assert!(board.starts_with("White hand: b g n 3p\n"));
+        assert!(board.contains("  .  l +R  .  .  P  .  .  .  b\n"));
+        assert!(board.ends_with("Black hand: S\nWhite to move, move 124\n"));
+    }
+
     #[test]
     fn test_gui_roundtrip_position_sfen() {
         let input = "position sfen 8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124\n";
-        let msg = GuiMessage::parse(&input).unwrap();
+        let msg = GuiMessage::parse(input).unwrap();
         let output = format!("{msg}\n");
         assert_eq!(output, input);
     }
@@ -215,7 +396,7 @@ mod tests {
     #[test]
     fn test_gui_roundtrip_position_startpos_moves() {
         let input = "position startpos moves 2g2f 8c8d 7g7f 3c3d\n";
-        let msg = GuiMessage::parse(&input).unwrap();
+        let msg = GuiMessage::parse(input).unwrap();
         let output = format!("{msg}\n");
         assert_eq!(output, input);
     }
@@ -249,7 +430,7 @@ mod tests {
             GuiMessage::UsiNewGame,
             GuiMessage::Position {
                 sfen: None,
-                moves: Some(moves),
+                moves: Some(moves.into()),
             },
             GuiMessage::Go(params),
         ];
@@ -277,6 +458,28 @@ mod tests {
         assert_eq!(msg, EngineMessage::Unknown(s("yoho ")));
     }
 
+    #[test]
+    fn test_engine_usiok_trailing_tokens() {
+        // Zero-argument commands with trailing tokens are not recognized. The whole
+        // line is kept as `Unknown`, so nothing is silently dropped.
+        let msg = EngineMessage::parse("usiok foo\n").unwrap();
+        assert_eq!(msg, EngineMessage::Unknown(s("usiok foo\n")));
+        let msg = EngineMessage::parse("readyok bar\n").unwrap();
+        assert_eq!(msg, EngineMessage::Unknown(s("readyok bar\n")));
+        // trailing white space is fine
+        let msg = EngineMessage::parse("usiok \n").unwrap();
+        assert_eq!(msg, EngineMessage::UsiOk);
+        // the next line is parsed normally
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new("usiok foo\nreadyok\n").collect();
+        assert_eq!(
+            msgs,
+            vec![
+                EngineMessage::Unknown(s("usiok foo\n")),
+                EngineMessage::ReadyOk
+            ]
+        );
+    }
+
     #[test]
     fn test_engine_usiok_cr() {
         let msg = EngineMessage::parse("usiok\r").unwrap();
@@ -296,6 +499,31 @@ mod tests {
         assert_eq!(msg, EngineMessage::UsiOk);
     }
 
+    #[test]
+    fn test_engine_stream_incomplete_line() {
+        // a buffer ending in the middle of a message
+        let mut stream = EngineMessageStream::new("usiok\nbestmo");
+        assert_eq!(stream.next(), Some(EngineMessage::UsiOk));
+        assert_eq!(stream.next(), None);
+        assert_eq!(stream.incomplete(), "bestmo");
+
+        // the caller buffers the fragment for the next read
+        let next = format!("{}{}", stream.incomplete(), "ve 7g7f\r\n");
+        let stream = EngineMessageStream::new(&next);
+        assert_eq!(stream.incomplete(), "");
+        let msgs: Vec<EngineMessage> = stream.collect();
+        assert_eq!(msgs, vec![EngineMessage::parse("bestmove 7g7f\n").unwrap()]);
+
+        // no complete line at all
+        let mut stream = EngineMessageStream::new("info dep");
+        assert_eq!(stream.next(), None);
+        assert_eq!(stream.incomplete(), "info dep");
+        assert_eq!(EngineMessageStream::new("").incomplete(), "");
+
+        // try_parse stays strict
+        assert!(EngineMessageStream::try_parse("usiok\nbestmo").is_err());
+    }
+
     #[test]
     fn test_engine_roundtrip_usiok() {
         let msg = EngineMessage::UsiOk;
@@ -320,6 +548,17 @@ mod tests {
         assert_eq!(input, format!("{msg}\n"));
     }
 
+    #[test]
+    fn test_engine_id_name_and_author() {
+        // the name is the rest of the line, even if it looks like it contains the author
+        let input = "id name Foo author Bar\n";
+        let msg = EngineMessage::parse(input).unwrap();
+        assert_eq!(msg, EngineMessage::Id(IdParams::Name(s("Foo author Bar"))));
+        assert_eq!(input, format!("{msg}\n"));
+        let msg = EngineMessage::parse("id author Bar name Foo\n").unwrap();
+        assert_eq!(msg, EngineMessage::Id(IdParams::Author(s("Bar name Foo"))));
+    }
+
     #[test]
     fn test_engine_option_check() {
         let input = "option name Nullmove type check default true\n";
@@ -435,7 +674,7 @@ mod tests {
             msg,
             EngineMessage::Info(vec![InfoParam::CurrLine {
                 cpu_nr: None,
-                line: line
+                line: line.into()
             }])
         );
         assert_eq!(format!("{msg}\n"), input);
@@ -454,12 +693,135 @@ mod tests {
             msg,
             EngineMessage::Info(vec![InfoParam::CurrLine {
                 cpu_nr: Some(3),
-                line: line
+                line: line.into()
             }])
         );
         assert_eq!(format!("{msg}\n"), input);
     }
 
+    #[test]
+    fn test_engine_roundtrip_status_check() {
+        for (word, state) in [
+            ("checking", StatusCheck::Checking),
+            ("ok", StatusCheck::Ok),
+            ("error", StatusCheck::Error),
+        ] {
+            let input = format!("copyprotection {word}\n");
+            let msg = EngineMessage::parse(&input).unwrap();
+            assert_eq!(msg, EngineMessage::CopyProtection(state));
+            assert_eq!(msg.to_usi_line(), input);
+
+            let input = format!("registration {word}\n");
+            let msg = EngineMessage::parse(&input).unwrap();
+            assert_eq!(msg, EngineMessage::Registration(state));
+            assert_eq!(msg.to_usi_line(), input);
+        }
+    }
+
+    #[test]
+    fn test_engine_spin_option_any_order() {
+        let canonical =
+            EngineMessage::parse("option name X type spin default 2 min 0 max 4\n").unwrap();
+        assert_eq!(
+            canonical,
+            EngineMessage::Option(OptionParam::Spin {
+                name: s("X"),
+                default: Some(2),
+                min: Some(0),
+                max: Some(4),
+            })
+        );
+        for input in [
+            "option name X type spin min 0 max 4 default 2\n",
+            "option name X type spin max 4 default 2 min 0\n",
+            "option name X type spin min 0 default 2 max 4\n",
+        ] {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert_eq!(msg, canonical);
+            assert_eq!(
+                msg.to_string(),
+                "option name X type spin default 2 min 0 max 4"
+            );
+        }
+        let msg = EngineMessage::parse("option name X type spin max 10\n").unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Option(OptionParam::Spin {
+                name: s("X"),
+                default: None,
+                min: None,
+                max: Some(10),
+            })
+        );
+        // each sub-key at most once
+        for input in [
+            "option name X type spin default 1 default 2\n",
+            "option name X type spin min 0 max 4 min 1\n",
+            "option name X type spin max 4 default 2 max 5\n",
+        ] {
+            assert!(EngineMessage::parse(input).unwrap().is_unknown(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_engine_option_without_default() {
+        let cases = [
+            (
+                "option name X type spin min 0 max 100\n",
+                OptionParam::Spin {
+                    name: s("X"),
+                    default: None,
+                    min: Some(0),
+                    max: Some(100),
+                },
+            ),
+            (
+                "option name Style type combo var Solid var Wild\n",
+                OptionParam::Combo {
+                    name: s("Style"),
+                    default: None,
+                    vars: vec![s("Solid"), s("Wild")],
+                },
+            ),
+            (
+                "option name Nullmove type check\n",
+                OptionParam::Check {
+                    name: s("Nullmove"),
+                    default: None,
+                },
+            ),
+        ];
+        for (input, param) in cases {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert_eq!(msg, EngineMessage::Option(param), "{input}");
+            assert_eq!(msg.to_usi_line(), input);
+        }
+    }
+
+    #[test]
+    fn test_engine_combo_option_default_after_vars() {
+        let canonical = "option name Style type combo default Wild var Solid var Wild\n";
+        for input in [
+            "option name Style type combo var Solid var Wild default Wild\n",
+            "option name Style type combo var Solid default Wild var Wild\n",
+        ] {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert_eq!(
+                msg,
+                EngineMessage::Option(OptionParam::Combo {
+                    name: s("Style"),
+                    default: Some(s("Wild")),
+                    vars: vec![s("Solid"), s("Wild")],
+                }),
+                "{input}"
+            );
+            assert_eq!(msg.to_usi_line(), canonical);
+        }
+        // at most one default
+        let input = "option name Style type combo default Solid var Solid default Wild var Wild\n";
+        assert!(EngineMessage::parse(input).unwrap().is_unknown());
+    }
+
     #[test]
     fn test_engine_message_stream1() {
         let input = "\
@@ -501,6 +863,90 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_engine_option_name_and_type() {
+        let input = "\
+            id name haitaka-shogi
+            option name Nullmove type check default true
+            option name Selectivity type spin default 2 min 0 max 4
+            option name Style type combo default Normal var Solid var Normal var Wild
+            option name USI_Ponder type check default false
+            option name Clear_Hash type button
+            option name BookFile type filename default book.bin
+            option name Greeting type string default hello
+            usiok
+        ";
+        let options: Vec<OptionParam> = EngineMessageStream::new(input)
+            .filter_map(|msg| match msg {
+                EngineMessage::Option(opt) => Some(opt),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<&str> = options.iter().map(|opt| opt.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Nullmove",
+                "Selectivity",
+                "Style",
+                "USI_Ponder",
+                "Clear_Hash",
+                "BookFile",
+                "Greeting"
+            ]
+        );
+        let types: Vec<&str> = options.iter().map(|opt| opt.type_str()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "check", "spin", "combo", "check", "button", "filename", "string"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_engine_info_tbhits_sbhits() {
+        let input = "info nodes 1000 tbhits 5 sbhits 2\n";
+        let msg = EngineMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![
+                InfoParam::Nodes(1000),
+                InfoParam::TbHits(5),
+                InfoParam::SbHits(2),
+            ])
+        );
+        assert_eq!(msg.to_usi_line(), input);
+    }
+
+    #[test]
+    fn test_engine_until_bestmove() {
+        let input = "\
+            info depth 1 score cp 20 pv 7g7f
+            info string thinking
+            info depth 2 score cp 35 pv 7g7f 3c3d
+            bestmove 7g7f ponder 3c3d
+            info depth 1
+            readyok
+        ";
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(input).until_bestmove().collect();
+        assert_eq!(msgs.len(), 4);
+        assert!(msgs[..3].iter().all(|msg| msg.is_info()));
+        assert_eq!(
+            msgs[3],
+            EngineMessage::BestMove(BestMoveParams::BestMove {
+                bestmove: "7g7f".parse().unwrap(),
+                ponder: Some("3c3d".parse().unwrap()),
+            })
+        );
+
+        // without a bestmove, all messages are returned
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new("info depth 1\ninfo depth 2\n")
+            .until_bestmove()
+            .collect();
+        assert_eq!(msgs.len(), 2);
+    }
+
     #[test]
     fn test_engine_message_stream2() {
         let input = "\
@@ -522,7 +968,7 @@ mod tests {
                 InfoParam::Depth(1),
                 InfoParam::Nodes(13),
                 InfoParam::Time(Duration::from_millis(15)),
-                InfoParam::Pv(vec!["2g2f".parse::<Move>().unwrap()]),
+                InfoParam::Pv("2g2f".parse().unwrap()),
             ]),
             EngineMessage::Info(vec![
                 InfoParam::CurrMove("2g2f".parse::<Move>().unwrap()),
@@ -537,12 +983,15 @@ mod tests {
             EngineMessage::Info(vec![
                 InfoParam::ScoreCp(156, ScoreBound::Exact),
                 InfoParam::MultiPv(1),
-                InfoParam::Pv(vec![
-                    "P*5h".parse::<Move>().unwrap(),
-                    "4g5g".parse::<Move>().unwrap(),
-                    "5h5g".parse::<Move>().unwrap(),
-                    "8b8f".parse::<Move>().unwrap(),
-                ]),
+                InfoParam::Pv(
+                    vec![
+                        "P*5h".parse::<Move>().unwrap(),
+                        "4g5g".parse::<Move>().unwrap(),
+                        "5h5g".parse::<Move>().unwrap(),
+                        "8b8f".parse::<Move>().unwrap(),
+                    ]
+                    .into(),
+                ),
             ]),
         ];
         let stream = EngineMessageStream::parse(input);
@@ -550,4 +999,3223 @@ mod tests {
             assert_eq!(parsed, expected);
         }
     }
+
+    //
+    // helpers
+    //
+
+    #[test]
+    fn test_join_moves() {
+        let moves: [Move; 3] = [
+            "7g7f".parse().unwrap(),
+            "P*5e".parse().unwrap(),
+            "2b3a+".parse().unwrap(),
+        ];
+        assert_eq!(join_moves(&moves), "7g7f P*5e 2b3a+");
+        assert_eq!(join_moves(&moves[1..]), "P*5e 2b3a+");
+        assert_eq!(join_moves(&[]), "");
+        assert_eq!(join_display(&moves, ","), "7g7f,P*5e,2b3a+");
+        assert_eq!(
+            join_display(&[InfoParam::Depth(3), InfoParam::Nodes(10)], " "),
+            "depth 3 nodes 10"
+        );
+        let v = moves.to_vec();
+        assert_eq!(format_vec!(v), join_moves(&v));
+    }
+
+    #[test]
+    fn test_moves_to_usi() {
+        let input = "7g7f 3c3d P*5e 2b3a+";
+        let EngineMessage::Info(info) = EngineMessage::parse(format!("info pv {input}\n")).unwrap()
+        else {
+            panic!("expected info");
+        };
+        let InfoParam::Pv(moves) = &info[0] else {
+            panic!("expected pv");
+        };
+        assert_eq!(moves_to_usi(moves), input);
+        assert_eq!(format!("pv {}", moves_to_usi(moves)), info[0].to_string());
+    }
+
+    //
+    // durations
+    //
+
+    #[test]
+    fn test_info_time_whole_millis_roundtrip() {
+        let msg = EngineMessage::Info(vec![InfoParam::Time(Duration::from_millis(1500))]);
+        let input = format!("{msg}\n");
+        assert_eq!(input, "info time 1500\n");
+        assert_eq!(EngineMessage::parse(&input).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_info_time_micros_rounded() {
+        // 1500 micros is rounded (not truncated) to 2 millis
+        let msg = EngineMessage::Info(vec![InfoParam::Time(Duration::from_micros(1500))]);
+        let input = format!("{msg}\n");
+        assert_eq!(input, "info time 2\n");
+        assert_eq!(
+            EngineMessage::parse(&input).unwrap(),
+            EngineMessage::Info(vec![InfoParam::Time(round_to_millis(
+                Duration::from_micros(1500)
+            ))])
+        );
+
+        let msg = EngineMessage::Info(vec![InfoParam::Time(Duration::from_micros(1499))]);
+        assert_eq!(format!("{msg}\n"), "info time 1\n");
+    }
+
+    #[test]
+    fn test_go_time_micros_normalized() {
+        let params = EngineParams::new().btime(Duration::from_micros(1500));
+        assert_eq!(params, EngineParams::new().btime(2));
+        let msg = GuiMessage::Go(params);
+        let s = format!("{msg}\n");
+        assert_eq!(s, "go btime 2\n");
+        assert_eq!(GuiMessage::parse(&s).unwrap(), msg);
+    }
+
+    //
+    // semantic equality
+    //
+
+    #[test]
+    fn test_gui_whitespace_insignificant() {
+        let msg1 = GuiMessage::parse("go  btime  1000\n").unwrap();
+        let msg2 = GuiMessage::parse("go btime 1000\n").unwrap();
+        assert_eq!(msg1, msg2);
+    }
+
+    #[test]
+    fn test_engine_info_semantically_eq() {
+        let msg1 = EngineMessage::parse("info depth 3 nodes 1000 score cp 20 pv 7g7f\n").unwrap();
+        let msg2 =
+            EngineMessage::parse("info   score cp 20 pv 7g7f depth 3 nodes  1000\n").unwrap();
+        assert_ne!(msg1, msg2);
+        assert!(msg1.semantically_eq(&msg2));
+        assert!(msg2.semantically_eq(&msg1));
+
+        // same params, but different multiplicity
+        let msg3 = EngineMessage::parse("info depth 3 depth 3 nodes 1000\n").unwrap();
+        let msg4 = EngineMessage::parse("info depth 3 nodes 1000 nodes 1000\n").unwrap();
+        assert!(!msg3.semantically_eq(&msg4));
+
+        let msg5 = EngineMessage::parse("info depth 4 nodes 1000 score cp 20 pv 7g7f\n").unwrap();
+        assert!(!msg1.semantically_eq(&msg5));
+
+        assert!(EngineMessage::UsiOk.semantically_eq(&EngineMessage::UsiOk));
+        assert!(!EngineMessage::UsiOk.semantically_eq(&EngineMessage::ReadyOk));
+    }
+
+    //
+    // scripts
+    //
+
+    #[test]
+    fn test_parse_gui_script_line_numbers() {
+        let script = "\
+            usi
+            isready
+
+            usinewgame
+
+            position startpos moves 7g7f 3c3d
+            go btime 300000 wtime 300000 byoyomi 5000
+            yoho
+            stop
+            quit
+            ";
+        let msgs = parse_gui_script(script);
+        let lines: Vec<usize> = msgs.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![1, 2, 4, 6, 7, 8, 9, 10]);
+        assert_eq!(msgs[0].1, GuiMessage::Usi);
+        assert_eq!(msgs[2].1, GuiMessage::UsiNewGame);
+        assert!(matches!(msgs[3].1, GuiMessage::Position { .. }));
+        assert!(matches!(msgs[4].1, GuiMessage::Go(_)));
+        assert!(matches!(msgs[5].1, GuiMessage::Unknown(_)));
+        assert_eq!(msgs[7].1, GuiMessage::Quit);
+    }
+
+    #[test]
+    fn test_parse_gui_script_without_final_newline() {
+        let msgs = parse_gui_script("usi\nisready");
+        assert_eq!(msgs, vec![(1, GuiMessage::Usi), (2, GuiMessage::IsReady)]);
+    }
+
+    //
+    // leading whitespace
+    //
+
+    const INDENTS: [&str; 4] = ["\t  ", "   ", "\t", " \t "];
+
+    #[test]
+    fn test_gui_leading_whitespace() {
+        assert_eq!(GuiMessage::parse("\t  usi\n").unwrap(), GuiMessage::Usi);
+        assert_eq!(
+            GuiMessage::parse("   go depth 3\n").unwrap(),
+            GuiMessage::Go(EngineParams::new().depth(3))
+        );
+
+        let commands = [
+            "usi",
+            "debug on",
+            "isready",
+            "setoption name USI_Hash value 128",
+            "register later",
+            "usinewgame",
+            "position startpos moves 7g7f",
+            "go btime 1000 wtime 1000 byoyomi 100",
+            "stop",
+            "ponderhit",
+            "gameover win",
+            "quit",
+        ];
+        for indent in INDENTS {
+            for cmd in commands {
+                let expected = GuiMessage::parse(format!("{cmd}\n")).unwrap();
+                assert!(!matches!(expected, GuiMessage::Unknown(_)));
+                let msg = GuiMessage::parse(format!("{indent}{cmd}\n")).unwrap();
+                assert_eq!(msg, expected, "indent {indent:?} before {cmd:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_leading_whitespace() {
+        assert_eq!(
+            EngineMessage::parse("\t  info string hello world\n").unwrap(),
+            EngineMessage::Info(vec![InfoParam::String(s("hello world"))])
+        );
+
+        let commands = [
+            "id name haitaka-shogi",
+            "usiok",
+            "readyok",
+            "bestmove 7g7f ponder 3c3d",
+            "copyprotection ok",
+            "registration ok",
+            "option name USI_Ponder type check default false",
+            "info depth 3 score cp 10 pv 7g7f",
+            "info string 7g7f (70%)",
+        ];
+        for indent in INDENTS {
+            for cmd in commands {
+                let expected = EngineMessage::parse(format!("{cmd}\n")).unwrap();
+                assert!(!matches!(expected, EngineMessage::Unknown(_)));
+                let msg = EngineMessage::parse(format!("{indent}{cmd}\n")).unwrap();
+                assert_eq!(msg, expected, "indent {indent:?} before {cmd:?}");
+            }
+        }
+    }
+
+    //
+    // inner whitespace
+    //
+
+    /// Replace each single space between fields by `sep`.
+    fn spread(cmd: &str, sep: &str) -> String {
+        cmd.replace(' ', sep)
+    }
+
+    const SEPARATORS: [&str; 4] = ["  ", "\t", " \t ", "\t\t"];
+
+    #[test]
+    fn test_gui_inner_whitespace() {
+        let commands = [
+            "setoption name USI_Hash value 256",
+            "setoption name USI_Ponder",
+            "go btime 1000 wtime 2000 byoyomi 100",
+            "go ponder binc 10 winc 10 searchmoves 7g7f 2g2f",
+            "go mate infinite",
+            "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1 moves 7g7f",
+        ];
+        for sep in SEPARATORS {
+            for cmd in commands {
+                let expected = GuiMessage::parse(format!("{cmd}\n")).unwrap();
+                assert!(!matches!(expected, GuiMessage::Unknown(_)));
+                let msg = GuiMessage::parse(format!("{}\n", spread(cmd, sep))).unwrap();
+                assert_eq!(msg, expected, "separator {sep:?} in {cmd:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_inner_whitespace() {
+        let commands = [
+            "option name USI_Hash type spin default 256 min 1 max 1024",
+            "option name USI_Ponder type check default true",
+            "option name BookFile type filename default book.db",
+            "option name Style type combo default Solid var Solid var Wild",
+            "info depth 3 score cp 10 lowerbound pv 7g7f 3c3d",
+            "bestmove 7g7f ponder 3c3d",
+        ];
+        for sep in SEPARATORS {
+            for cmd in commands {
+                let expected = EngineMessage::parse(format!("{cmd}\n")).unwrap();
+                assert!(!matches!(expected, EngineMessage::Unknown(_)));
+                let msg = EngineMessage::parse(format!("{}\n", spread(cmd, sep))).unwrap();
+                assert_eq!(msg, expected, "separator {sep:?} in {cmd:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_free_text_keeps_inner_whitespace() {
+        // `id` and `setoption ... value` take the rest of the line, `info string` too,
+        // and combo values are free text as well
+        assert_eq!(
+            EngineMessage::parse("id\tname   Foo  Bar\n").unwrap(),
+            EngineMessage::Id(IdParams::Name(s("Foo  Bar")))
+        );
+        assert_eq!(
+            EngineMessage::parse("info\tstring  hello \t world\n").unwrap(),
+            EngineMessage::Info(vec![InfoParam::String(s("hello \t world"))])
+        );
+        assert_eq!(
+            GuiMessage::parse("setoption\tname  Greeting  value  Hello   World\n").unwrap(),
+            GuiMessage::SetOption {
+                name: s("Greeting"),
+                value: Some(s("Hello   World"))
+            }
+        );
+        assert_eq!(
+            EngineMessage::parse(
+                "option name Style type combo default Very  Solid var Very  Solid\n"
+            )
+            .unwrap(),
+            EngineMessage::Option(OptionParam::Combo {
+                name: s("Style"),
+                default: Some(s("Very  Solid")),
+                vars: vec![s("Very  Solid")]
+            })
+        );
+    }
+
+    //
+    // parse options
+    //
+
+    fn repeat_moves(mv: &str, n: usize) -> String {
+        vec![mv; n].join(" ")
+    }
+
+    #[test]
+    fn test_engine_overlong_pv_truncated() {
+        let input = format!("info depth 1 pv {}\n", repeat_moves("7g7f", 5000));
+        let msg = EngineMessage::parse(&input).unwrap();
+        let EngineMessage::Info(params) = msg else {
+            panic!("expected info");
+        };
+        assert_eq!(params[0], InfoParam::Depth(1));
+        let InfoParam::Pv(pv) = &params[1] else {
+            panic!("expected pv");
+        };
+        assert_eq!(pv.len(), DEFAULT_MAX_MOVES);
+    }
+
+    #[test]
+    fn test_gui_overlong_searchmoves_truncated() {
+        let input = format!(
+            "go btime 1000 wtime 1000 searchmoves {}\n",
+            repeat_moves("P*5e 7g7f", 5000)
+        );
+        let msg = GuiMessage::parse(&input).unwrap();
+        let expected: MoveList = repeat_moves("P*5e 7g7f", DEFAULT_MAX_MOVES / 2)
+            .parse()
+            .unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(
+                EngineParams::new()
+                    .btime(1000)
+                    .wtime(1000)
+                    .searchmoves(expected)
+            )
+        );
+
+        // with a cap of zero the searchmoves are dropped altogether
+        let options = ParseOptions {
+            max_moves: 0,
+            ..ParseOptions::default()
+        };
+        let msg = GuiMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().btime(1000).wtime(1000))
+        );
+    }
+
+    #[test]
+    fn test_engine_max_moves_option() {
+        let options = ParseOptions {
+            max_moves: 3,
+            ..ParseOptions::default()
+        };
+        let mv: Move = "7g7f".parse().unwrap();
+        let input = format!("info pv {}\n", repeat_moves("7g7f", 10));
+        let msg = EngineMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![InfoParam::Pv(vec![mv; 3].into())])
+        );
+
+        let input = format!("info refutation {}\n", repeat_moves("7g7f", 10));
+        let msg = EngineMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![InfoParam::Refutation(vec![mv; 3].into())])
+        );
+
+        let input = format!("info currline 1 {}\n", repeat_moves("7g7f", 10));
+        let msg = EngineMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![InfoParam::CurrLine {
+                cpu_nr: Some(1),
+                line: vec![mv; 3].into()
+            }])
+        );
+    }
+
+    #[test]
+    fn test_gui_max_moves_option() {
+        let options = ParseOptions {
+            max_moves: 2,
+            ..ParseOptions::default()
+        };
+        let mv: Move = "7g7f".parse().unwrap();
+        let input = format!("go depth 5 searchmoves {}\n", repeat_moves("7g7f", 10));
+        let msg = GuiMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().depth(5).searchmoves(vec![mv; 2]))
+        );
+
+        // position moves are never truncated
+        let input = format!("position startpos moves {}\n", repeat_moves("7g7f", 10));
+        let msg = GuiMessage::parse_with(&input, &options).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Position {
+                sfen: None,
+                moves: Some(vec![mv; 10].into())
+            }
+        );
+    }
+
+    #[test]
+    fn test_separator_option() {
+        let options = ParseOptions {
+            separator: Some(';'),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            GuiMessage::parse_all_with("usinewgame; isready\n", &options).unwrap(),
+            vec![GuiMessage::UsiNewGame, GuiMessage::IsReady]
+        );
+        assert_eq!(
+            GuiMessage::parse_all_with("usi;isready\r\nposition startpos ; go depth 1\n", &options)
+                .unwrap(),
+            vec![
+                GuiMessage::Usi,
+                GuiMessage::IsReady,
+                GuiMessage::Position {
+                    sfen: None,
+                    moves: None
+                },
+                GuiMessage::Go(EngineParams::new().depth(1)),
+            ]
+        );
+        assert_eq!(
+            EngineMessage::parse_all_with("usiok;readyok\n", &options).unwrap(),
+            vec![EngineMessage::UsiOk, EngineMessage::ReadyOk]
+        );
+
+        // strict mode: one message per line
+        let msgs =
+            GuiMessage::parse_all_with("usinewgame; isready\n", &ParseOptions::default()).unwrap();
+        assert!(msgs.iter().all(|msg| msg.is_unknown()));
+
+        // other separators, combined with case-insensitivity
+        let options = ParseOptions {
+            separator: Some('|'),
+            case_insensitive: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            GuiMessage::parse_all_with("USI | IsReady\n", &options).unwrap(),
+            vec![GuiMessage::Usi, GuiMessage::IsReady]
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_option() {
+        // strict by default
+        assert_eq!(
+            GuiMessage::parse("USI\n").unwrap(),
+            GuiMessage::Unknown(s("USI"))
+        );
+        assert_eq!(
+            GuiMessage::parse_with("USI\n", &ParseOptions::default()).unwrap(),
+            GuiMessage::Unknown(s("USI"))
+        );
+
+        let options = ParseOptions {
+            case_insensitive: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            GuiMessage::parse_with("USI\n", &options).unwrap(),
+            GuiMessage::Usi
+        );
+        assert_eq!(
+            GuiMessage::parse_with("  IsReady\r\n", &options).unwrap(),
+            GuiMessage::IsReady
+        );
+        assert_eq!(
+            GuiMessage::parse_with("Position startpos moves 7g7f\n", &options).unwrap(),
+            GuiMessage::Position {
+                sfen: None,
+                moves: Some("7g7f".parse().unwrap())
+            }
+        );
+        assert_eq!(
+            EngineMessage::parse_with("USIOK\n", &options).unwrap(),
+            EngineMessage::UsiOk
+        );
+        // only the command keyword is case-insensitive
+        assert!(matches!(
+            GuiMessage::parse_with("GO DEPTH 3\n", &options).unwrap(),
+            GuiMessage::Unknown(_)
+        ));
+    }
+
+    //
+    // go serialization
+    //
+
+    #[test]
+    fn test_gui_roundtrip_go_time_control() {
+        let input = "go btime 300000 wtime 290000 binc 1000 winc 2000\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(
+                EngineParams::new()
+                    .btime(300000)
+                    .wtime(290000)
+                    .binc(1000)
+                    .winc(2000)
+            )
+        );
+        assert_eq!(format!("{msg}\n"), input);
+
+        let input = "go ponder btime 60000 wtime 60000 byoyomi 10000\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        assert_eq!(format!("{msg}\n"), input);
+    }
+
+    #[test]
+    fn test_gui_go_with_params() {
+        let GuiMessage::Go(base) = GuiMessage::parse("go depth 8 nodes 100000\n").unwrap() else {
+            panic!("expected go");
+        };
+        let deeper = base.with_depth(12);
+        assert_eq!(deeper, EngineParams::new().depth(12).nodes(100000));
+        assert_eq!(base, EngineParams::new().depth(8).nodes(100000));
+        assert_eq!(
+            GuiMessage::Go(deeper.with_movetime(3000)).to_usi_line(),
+            "go depth 12 nodes 100000 movetime 3000\n"
+        );
+
+        let clock = EngineParams::new().btime(60000).wtime(60000).byoyomi(10000);
+        let later = clock.with_btime(42000).with_wtime(55000);
+        assert_eq!(
+            GuiMessage::Go(later).to_usi_line(),
+            "go btime 42000 wtime 55000 byoyomi 10000\n"
+        );
+        assert_eq!(
+            GuiMessage::Go(clock).to_usi_line(),
+            "go btime 60000 wtime 60000 byoyomi 10000\n"
+        );
+    }
+
+    #[test]
+    fn test_gui_go_mate_interpretations() {
+        // default: milliseconds
+        let msg = GuiMessage::parse("go mate 5\n").unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().mate(MateParam::Timeout(Duration::from_millis(5))))
+        );
+        assert_eq!(msg.to_usi_line(), "go mate 5\n");
+
+        // UCI style: number of moves
+        let options = ParseOptions {
+            mate_in_moves: true,
+            ..ParseOptions::default()
+        };
+        let msg = GuiMessage::parse_with("go mate 5\n", &options).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().mate(MateParam::Moves(5)))
+        );
+        assert_eq!(msg.to_usi_line(), "go mate 5\n");
+        let msg = GuiMessage::parse_with("go mate infinite\n", &options).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().mate(MateParam::Infinite))
+        );
+    }
+
+    #[test]
+    fn test_gui_go_zero_increment() {
+        let input = "go btime 60000 wtime 60000 binc 0 winc 0\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(
+                EngineParams::new()
+                    .btime(60000)
+                    .wtime(60000)
+                    .binc(Duration::ZERO)
+                    .winc(Duration::ZERO)
+            )
+        );
+        assert_ne!(
+            msg,
+            GuiMessage::Go(EngineParams::new().btime(60000).wtime(60000))
+        );
+        assert_eq!(msg.to_usi_line(), input);
+
+        let msg = GuiMessage::parse("go binc 0 winc 0\n").unwrap();
+        assert_eq!(msg.to_usi_line(), "go binc 0 winc 0\n");
+    }
+
+    #[test]
+    fn test_gui_roundtrip_go_search_control() {
+        let moves: Vec<Move> = vec!["7g7f".parse().unwrap(), "2g2f".parse().unwrap()];
+        let params = EngineParams::new()
+            .movestogo(40)
+            .depth(12)
+            .nodes(1_000_000)
+            .movetime(5000)
+            .searchmoves(moves);
+        let msg = GuiMessage::Go(params);
+        let s = format!("{msg}\n");
+        assert_eq!(
+            s,
+            "go movestogo 40 depth 12 nodes 1000000 movetime 5000 searchmoves 7g7f 2g2f\n"
+        );
+        assert_eq!(GuiMessage::parse(&s).unwrap(), msg);
+
+        for input in [
+            "go infinite\n",
+            "go mate 5000\n",
+            "go mate infinite\n",
+            "go\n",
+        ] {
+            let msg = GuiMessage::parse(input).unwrap();
+            assert!(matches!(msg, GuiMessage::Go(_)));
+            assert_eq!(format!("{msg}\n"), input);
+        }
+    }
+
+    //
+    // input types
+    //
+
+    #[test]
+    fn test_parse_input_types() {
+        let owned = String::from("isready\n");
+        assert_eq!(GuiMessage::parse(&owned).unwrap(), GuiMessage::IsReady);
+        assert_eq!(GuiMessage::parse("isready\n").unwrap(), GuiMessage::IsReady);
+        assert_eq!(
+            GuiMessage::parse(owned.clone()).unwrap(),
+            GuiMessage::IsReady
+        );
+
+        let cow: std::borrow::Cow<str> = std::borrow::Cow::Borrowed("readyok\n");
+        assert_eq!(EngineMessage::parse(&cow).unwrap(), EngineMessage::ReadyOk);
+        assert_eq!(
+            EngineMessage::parse(String::from("usiok\n")).unwrap(),
+            EngineMessage::UsiOk
+        );
+
+        let script = String::from("usi\nisready\n");
+        assert_eq!(GuiMessageStream::new(&script).count(), 2);
+        assert_eq!(GuiMessageStream::new(script.as_str()).count(), 2);
+        let engine = String::from("usiok\nreadyok\n");
+        assert_eq!(
+            EngineMessageStream::new(&engine).collect::<Vec<_>>(),
+            vec![EngineMessage::UsiOk, EngineMessage::ReadyOk]
+        );
+    }
+
+    //
+    // parse errors
+    //
+
+    #[test]
+    fn test_parse_error_missing_newline() {
+        let missing = |line: &str| ParseError::MissingNewline {
+            line: line.to_string(),
+        };
+        assert_eq!(GuiMessage::parse("usi"), Err(missing("usi")));
+        assert_eq!(GuiMessage::parse(""), Err(missing("")));
+        assert_eq!(
+            EngineMessage::parse("usiok\nbestmo"),
+            Err(missing("bestmo"))
+        );
+        assert!(GuiMessageStream::try_parse("usi\nisready").is_err());
+        assert!(EngineMessageStream::try_parse("usiok\nreadyok\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_error_syntax() {
+        use crate::parser::{Rule, UsiParser};
+        use pest::Parser;
+
+        // Every newline-terminated input matches `Rule::start` (non-protocol lines
+        // become `Unknown`), so a syntax error needs a stricter rule.
+        let input = "isready\n";
+        let err = UsiParser::parse(Rule::usi, input).unwrap_err();
+        let err = ParseError::from_pest(err, input);
+        let ParseError::Syntax {
+            line, col, message, ..
+        } = &err
+        else {
+            panic!("expected syntax error");
+        };
+        assert_eq!((*line, *col), (1, 1));
+        assert!(message.contains("usi"));
+        assert_eq!(err.to_string(), format!("syntax error at 1:1: {message}"));
+        assert!(!err.to_string().contains('\n'));
+        // the verbose report shows the input
+        assert!(err.verbose().lines().count() > 1);
+        assert!(err.verbose().contains("isready"));
+    }
+
+    #[test]
+    fn test_parse_error_display_missing_newline() {
+        let err = GuiMessage::parse("usi").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected newline-terminated USI message, got \"usi\""
+        );
+        assert_eq!(err.verbose(), err.to_string());
+        // long input is truncated
+        let err = GuiMessage::parse(format!("setoption name {}", "x".repeat(50))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected newline-terminated USI message, got \"setoption name xxxxxxxxxxxxxxxxxxxxxxxxx...\""
+        );
+    }
+
+    #[test]
+    fn test_errors_as_trait_objects() {
+        use std::error::Error;
+
+        fn resulting_sfen(input: &str) -> Result<String, Box<dyn Error>> {
+            let msg = GuiMessage::parse(input)?;
+            Ok(msg.resulting_sfen()?)
+        }
+
+        assert_eq!(
+            resulting_sfen("position startpos moves 7g7f\n").unwrap(),
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2"
+        );
+
+        let err = resulting_sfen("position startpos").unwrap_err();
+        assert!(err.is::<ParseError>());
+        assert_eq!(
+            err.to_string(),
+            "expected newline-terminated USI message, got \"position startpos\""
+        );
+        assert!(err.source().is_none());
+
+        let err = resulting_sfen("position startpos moves 7g7e\n").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PositionError>(),
+            Some(&PositionError::IllegalMove {
+                index: 0,
+                mv: "7g7e".parse().unwrap()
+            })
+        );
+
+        let err = resulting_sfen("isready\n").unwrap_err();
+        assert_eq!(err.to_string(), "not a position message");
+
+        let err: Box<dyn Error> = EngineParams::new()
+            .searchmoves_from_strs(&["oops"])
+            .unwrap_err()
+            .into();
+        assert!(err.is::<InvalidMoves>());
+    }
+
+    //
+    // score bounds
+    //
+
+    #[test]
+    fn test_score_roundtrip() {
+        for (input, param) in [
+            (
+                "info score cp 50\n",
+                InfoParam::ScoreCp(50, ScoreBound::Exact),
+            ),
+            (
+                "info score cp -30 upperbound\n",
+                InfoParam::ScoreCp(-30, ScoreBound::Upper),
+            ),
+            (
+                "info score mate 3\n",
+                InfoParam::ScoreMate(Some(3), ScoreBound::Exact),
+            ),
+            (
+                "info score mate 3 lowerbound\n",
+                InfoParam::ScoreMate(Some(3), ScoreBound::Lower),
+            ),
+            (
+                "info score mate -5 upperbound\n",
+                InfoParam::ScoreMate(Some(-5), ScoreBound::Upper),
+            ),
+            (
+                "info score mate +\n",
+                InfoParam::ScoreMate(None, ScoreBound::MatePlus),
+            ),
+            (
+                "info score mate -\n",
+                InfoParam::ScoreMate(None, ScoreBound::MateMin),
+            ),
+        ] {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert_eq!(msg, EngineMessage::Info(vec![param]));
+            assert_eq!(format!("{msg}\n"), input);
+        }
+    }
+
+    #[test]
+    fn test_score_bound_order() {
+        // a bound in front of the value is accepted, and emitted after it
+        let msg = EngineMessage::parse("info score mate lowerbound 3\n").unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![InfoParam::ScoreMate(Some(3), ScoreBound::Lower)])
+        );
+        assert_eq!(msg.to_string(), "info score mate 3 lowerbound");
+        let msg = EngineMessage::parse("info score cp upperbound 12 depth 2\n").unwrap();
+        assert_eq!(msg.to_string(), "info score cp 12 upperbound depth 2");
+
+        // the bare mate signs can not be combined with a bound
+        let msg = EngineMessage::parse("info score mate + upperbound\n").unwrap();
+        assert!(matches!(msg, EngineMessage::Unknown(_)));
+
+        assert_eq!(ScoreBound::Lower.to_string(), "lowerbound");
+        assert_eq!(ScoreBound::MateMin.to_string(), "-");
+        assert_eq!(ScoreBound::Exact.to_string(), "");
+    }
+
+    #[test]
+    fn test_score_bound_before_type() {
+        let expected = EngineMessage::Info(vec![InfoParam::ScoreCp(50, ScoreBound::Lower)]);
+        for input in [
+            "info score lowerbound cp 50\n",
+            "info score cp 50 lowerbound\n",
+            "info score cp lowerbound 50\n",
+        ] {
+            assert_eq!(EngineMessage::parse(input).unwrap(), expected, "{input}");
+        }
+
+        let msg = EngineMessage::parse("info score upperbound mate -5 pv 7g7f\n").unwrap();
+        assert_eq!(msg.to_string(), "info score mate -5 upperbound pv 7g7f");
+
+        // at most one bound
+        let msg = EngineMessage::parse("info score lowerbound cp 50 upperbound\n").unwrap();
+        assert!(msg.is_unknown());
+    }
+
+    //
+    // go modes
+    //
+
+    fn go_mode(input: &str) -> GoMode {
+        let GuiMessage::Go(params) = GuiMessage::parse(input).unwrap() else {
+            panic!("expected go: {input}");
+        };
+        params.interpret()
+    }
+
+    #[test]
+    fn test_go_interpret() {
+        assert_eq!(
+            go_mode("go ponder btime 1000 wtime 1000 byoyomi 100\n"),
+            GoMode::Ponder
+        );
+        assert_eq!(
+            go_mode("go mate 3000\n"),
+            GoMode::Mate(MateParam::Timeout(Duration::from_secs(3)))
+        );
+        assert_eq!(
+            go_mode("go mate infinite\n"),
+            GoMode::Mate(MateParam::Infinite)
+        );
+        assert_eq!(go_mode("go infinite\n"), GoMode::Infinite);
+        assert_eq!(go_mode("go\n"), GoMode::Infinite);
+        assert_eq!(go_mode("go depth 12 nodes 5000\n"), GoMode::FixedDepth(12));
+        assert_eq!(go_mode("go nodes 5000\n"), GoMode::FixedNodes(5000));
+        assert_eq!(
+            go_mode("go movetime 2500\n"),
+            GoMode::MoveTime(Duration::from_millis(2500))
+        );
+        assert_eq!(
+            go_mode("go btime 60000 wtime 50000 byoyomi 10000\n"),
+            GoMode::Clock {
+                btime: Some(Duration::from_secs(60)),
+                wtime: Some(Duration::from_secs(50)),
+                binc: None,
+                winc: None,
+                byoyomi: Some(Duration::from_secs(10)),
+                movestogo: None,
+            }
+        );
+        assert_eq!(
+            go_mode("go btime 300000 wtime 300000 binc 2000 winc 2000 movestogo 40\n"),
+            GoMode::Clock {
+                btime: Some(Duration::from_secs(300)),
+                wtime: Some(Duration::from_secs(300)),
+                binc: Some(Duration::from_secs(2)),
+                winc: Some(Duration::from_secs(2)),
+                byoyomi: None,
+                movestogo: Some(40),
+            }
+        );
+    }
+
+    //
+    // stream cloning
+    //
+
+    #[test]
+    fn test_stream_clone() {
+        let mut stream = GuiMessageStream::new("usi\nisready\nusinewgame\nquit\n");
+        assert_eq!(stream.next(), Some(GuiMessage::Usi));
+        let checkpoint = stream.clone();
+        let rest: Vec<GuiMessage> = stream.collect();
+        assert_eq!(
+            rest,
+            vec![
+                GuiMessage::IsReady,
+                GuiMessage::UsiNewGame,
+                GuiMessage::Quit
+            ]
+        );
+        assert_eq!(checkpoint.collect::<Vec<_>>(), rest);
+
+        let stream = EngineMessageStream::new("id name X\nusiok\nreadyok\n");
+        assert_eq!(
+            stream.clone().collect::<Vec<_>>(),
+            stream.collect::<Vec<_>>()
+        );
+    }
+
+    //
+    // move notation
+    //
+
+    #[test]
+    fn test_move_notation_roundtrip() {
+        // a normal move, a drop, and a promotion in each message type that carries moves
+        for input in [
+            "position startpos moves 7g7f P*5e 2b3a+\n",
+            "bestmove P*5e ponder 2b3a+\n",
+            "bestmove 2b3a+ ponder 7g7f\n",
+            "info depth 3 pv 7g7f P*5e 2b3a+\n",
+            "info refutation P*5e 2b3a+ 7g7f\n",
+            "info currline 1 2b3a+ P*5e 7g7f\n",
+            "go searchmoves P*5e 7g7f 2b3a+\n",
+        ] {
+            let output = match GuiMessage::parse(input).unwrap() {
+                GuiMessage::Unknown(_) => EngineMessage::parse(input).unwrap().to_string(),
+                msg => msg.to_string(),
+            };
+            assert_eq!(format!("{output}\n"), input);
+        }
+
+        let mv: Move = "P*5e".parse().unwrap();
+        assert!(mv.is_drop());
+        let mv: Move = "2b3a+".parse().unwrap();
+        assert!(matches!(
+            mv,
+            Move::BoardMove {
+                promotion: true,
+                ..
+            }
+        ));
+    }
+
+    //
+    // allocation reuse
+    //
+
+    // A global allocator which counts the allocations made by the current thread,
+    // so that tests running in parallel don't disturb each other.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.realloc(ptr, layout, size) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    fn count_allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(|n| n.get());
+        f();
+        ALLOCATIONS.with(|n| n.get()) - before
+    }
+
+    #[test]
+    fn test_parse_into_reuses_buffer() {
+        const N: usize = 1000;
+        let input = "info depth 10 seldepth 14 time 523 nodes 1000000 nps 1912045 hashfull 12\n";
+
+        let fresh = count_allocations(|| {
+            for _ in 0..N {
+                let msg = EngineMessage::parse(input).unwrap();
+                assert!(matches!(msg, EngineMessage::Info(ref v) if v.len() == 6));
+            }
+        });
+
+        let mut buf = Vec::new();
+        let reused = count_allocations(|| {
+            for _ in 0..N {
+                let msg = EngineMessage::parse_into(input, &mut buf).unwrap();
+                assert_eq!(msg, EngineMessage::Info(vec![]));
+                assert_eq!(buf.len(), 6);
+            }
+        });
+
+        // a fresh Vec of 6 params takes at least two (re)allocations per parse
+        assert!(
+            reused + N <= fresh,
+            "parse_into: {reused} allocations, parse: {fresh}"
+        );
+
+        let msg = EngineMessage::parse_into("usiok\n", &mut buf).unwrap();
+        assert_eq!(msg, EngineMessage::UsiOk);
+        assert!(buf.is_empty());
+    }
+
+    //
+    // writer
+    //
+
+    #[test]
+    fn test_message_writer() {
+        let expected = "\
+id name haitaka-shogi
+id author tofutofu
+option name Nullmove type check default true
+option name Selectivity type spin default 2 min 0 max 4
+option name Style type combo default Normal var Solid var Normal var Wild
+option name USI_Ponder type check default false
+usiok
+";
+        let mut writer = MessageWriter::new();
+        writer.extend(vec![
+            EngineMessage::Id(IdParams::Name(s("haitaka-shogi"))),
+            EngineMessage::Id(IdParams::Author(s("tofutofu"))),
+            EngineMessage::Option(OptionParam::Check {
+                name: s("Nullmove"),
+                default: Some(true),
+            }),
+            EngineMessage::Option(OptionParam::Spin {
+                name: s("Selectivity"),
+                default: Some(2),
+                min: Some(0),
+                max: Some(4),
+            }),
+            EngineMessage::Option(OptionParam::Combo {
+                name: s("Style"),
+                default: Some(s("Normal")),
+                vars: vec![s("Solid"), s("Normal"), s("Wild")],
+            }),
+        ]);
+        writer.extend(EngineMessageStream::new(
+            "option name USI_Ponder type check default false\nusiok\n",
+        ));
+        assert_eq!(writer.as_str(), expected);
+        assert_eq!(writer.into_string(), expected);
+
+        let mut writer = MessageWriter::new();
+        writer.extend([GuiMessage::Usi, GuiMessage::IsReady]);
+        assert_eq!(writer.into_string(), "usi\nisready\n");
+        assert_eq!(GuiMessage::Stop.to_usi_line(), "stop\n");
+    }
+
+    #[test]
+    fn test_handshake_response() {
+        let response = HandshakeResponse {
+            name: s("haitaka-shogi"),
+            author: s("tofutofu"),
+            options: vec![
+                OptionParam::Spin {
+                    name: s("USI_Hash"),
+                    default: Some(256),
+                    min: Some(1),
+                    max: Some(4096),
+                },
+                OptionParam::Check {
+                    name: s("USI_Ponder"),
+                    default: Some(false),
+                },
+            ],
+        };
+        let mut out: Vec<u8> = Vec::new();
+        response.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+id name haitaka-shogi
+id author tofutofu
+option name USI_Hash type spin default 256 min 1 max 4096
+option name USI_Ponder type check default false
+usiok
+"
+        );
+
+        let msgs = response.messages();
+        assert_eq!(msgs.len(), 5);
+        assert_eq!(msgs.last(), Some(&EngineMessage::UsiOk));
+    }
+
+    //
+    // strict collection
+    //
+
+    #[test]
+    fn test_try_collect_valid() {
+        let msgs = GuiMessageStream::new("usi\nisready\n\nusinewgame\n").try_collect_valid();
+        assert_eq!(
+            msgs,
+            Ok(vec![
+                GuiMessage::Usi,
+                GuiMessage::IsReady,
+                GuiMessage::UsiNewGame
+            ])
+        );
+
+        let msgs = GuiMessageStream::new("usi\ndebug xyz\nisready\n").try_collect_valid();
+        assert_eq!(msgs, Err((vec![GuiMessage::Usi], s("debug xyz"))));
+
+        let msgs = EngineMessageStream::new("id name X\nusiok\n").try_collect_valid();
+        assert_eq!(
+            msgs,
+            Ok(vec![
+                EngineMessage::Id(IdParams::Name(s("X"))),
+                EngineMessage::UsiOk
+            ])
+        );
+
+        let msgs = EngineMessageStream::new("usiok\nfoo bar\nreadyok\n").try_collect_valid();
+        assert_eq!(msgs, Err((vec![EngineMessage::UsiOk], s("foo bar"))));
+    }
+
+    //
+    // handshake timing
+    //
+
+    #[test]
+    fn test_handshake_timer() {
+        let base = std::time::Instant::now();
+        let millis = std::cell::Cell::new(0u64);
+        let mut timer = HandshakeTimer::with_clock(|| base + Duration::from_millis(millis.get()));
+
+        // readyok without isready is ignored
+        timer.observe(&EngineMessage::ReadyOk);
+        assert_eq!(timer.isready_to_readyok(), None);
+
+        timer.observe_gui(&GuiMessage::Usi);
+        millis.set(120);
+        timer.observe(&EngineMessage::Id(IdParams::Name(s("X"))));
+        millis.set(150);
+        timer.observe(&EngineMessage::UsiOk);
+        assert_eq!(timer.usi_to_usiok(), Some(Duration::from_millis(150)));
+
+        millis.set(1000);
+        timer.observe_gui(&GuiMessage::IsReady);
+        assert_eq!(timer.isready_to_readyok(), None);
+        millis.set(3500);
+        timer.observe(&EngineMessage::ReadyOk);
+        assert_eq!(
+            timer.isready_to_readyok(),
+            Some(Duration::from_millis(2500))
+        );
+
+        // a second readyok doesn't change the measurement
+        millis.set(9000);
+        timer.observe(&EngineMessage::ReadyOk);
+        assert_eq!(
+            timer.isready_to_readyok(),
+            Some(Duration::from_millis(2500))
+        );
+
+        // a new isready starts a new measurement
+        timer.observe_gui(&GuiMessage::IsReady);
+        millis.set(9010);
+        timer.observe(&EngineMessage::ReadyOk);
+        assert_eq!(timer.isready_to_readyok(), Some(Duration::from_millis(10)));
+        assert_eq!(timer.usi_to_usiok(), Some(Duration::from_millis(150)));
+    }
+
+    //
+    // predicates
+    //
+
+    #[test]
+    fn test_message_predicates() {
+        let gui = |input: &str| GuiMessage::parse(input).unwrap();
+        assert!(gui("usi\n").is_usi());
+        assert!(gui("isready\n").is_isready());
+        assert!(gui("position startpos moves 7g7f\n").is_position());
+        assert!(gui("go depth 3\n").is_go());
+        assert!(gui("stop\n").is_stop());
+        assert!(gui("quit\n").is_quit());
+        assert!(gui("yoho\n").is_unknown());
+        assert!(!gui("quit\n").is_go());
+        assert!(!gui("go depth 3\n").is_quit());
+
+        let engine = |input: &str| EngineMessage::parse(input).unwrap();
+        assert!(engine("usiok\n").is_usiok());
+        assert!(engine("readyok\n").is_readyok());
+        assert!(engine("bestmove 7g7f\n").is_bestmove());
+        assert!(engine("bestmove resign\n").is_bestmove());
+        assert!(engine("info depth 3\n").is_info());
+        assert!(engine("yoho\n").is_unknown());
+        assert!(!engine("info depth 3\n").is_bestmove());
+        assert!(!engine("usiok\n").is_readyok());
+    }
+
+    #[test]
+    fn test_message_kind() {
+        let gui = [
+            ("usi", "usi"),
+            ("debug on", "debug"),
+            ("isready", "isready"),
+            ("setoption name USI_Hash value 256", "setoption"),
+            ("register later", "register"),
+            ("usinewgame", "usinewgame"),
+            ("position startpos", "position"),
+            ("go infinite", "go"),
+            ("stop", "stop"),
+            ("ponderhit", "ponderhit"),
+            ("gameover win", "gameover"),
+            ("quit", "quit"),
+            ("yoho", "unknown"),
+        ];
+        for (input, kind) in gui {
+            assert_eq!(GuiMessage::from_line(input).kind(), kind, "{input}");
+        }
+
+        let engine = [
+            ("id name haitaka", "id"),
+            ("usiok", "usiok"),
+            ("readyok", "readyok"),
+            ("bestmove 7g7f", "bestmove"),
+            ("copyprotection ok", "copyprotection"),
+            ("registration checking", "registration"),
+            ("option name USI_Ponder type check default true", "option"),
+            ("info depth 1", "info"),
+            ("checkmate nomate", "checkmate"),
+            ("yoho", "unknown"),
+        ];
+        for (input, kind) in engine {
+            assert_eq!(EngineMessage::from_line(input).kind(), kind, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_message_accessors_cover_variants() {
+        type Is<T> = fn(&T) -> bool;
+        let gui_predicates: [(&str, Is<GuiMessage>); 13] = [
+            ("usi", GuiMessage::is_usi),
+            ("debug", GuiMessage::is_debug),
+            ("isready", GuiMessage::is_isready),
+            ("setoption", GuiMessage::is_setoption),
+            ("register", GuiMessage::is_register),
+            ("usinewgame", GuiMessage::is_usinewgame),
+            ("position", GuiMessage::is_position),
+            ("go", GuiMessage::is_go),
+            ("stop", GuiMessage::is_stop),
+            ("ponderhit", GuiMessage::is_ponderhit),
+            ("gameover", GuiMessage::is_gameover),
+            ("quit", GuiMessage::is_quit),
+            ("unknown", GuiMessage::is_unknown),
+        ];
+        let script = "usi\ndebug\nisready\nsetoption name A\nregister later\nusinewgame\n\
+            position startpos\ngo\nstop\nponderhit\ngameover draw\nquit\nyoho\n";
+        let msgs: Vec<GuiMessage> = GuiMessageStream::new(script).collect();
+        assert_eq!(msgs.len(), gui_predicates.len());
+        for msg in &msgs {
+            // exactly the predicate matching the kind holds
+            for (kind, is) in &gui_predicates {
+                assert_eq!(is(msg), msg.kind() == *kind, "{msg:?}");
+            }
+        }
+
+        let engine_predicates: [(&str, Is<EngineMessage>); 10] = [
+            ("id", EngineMessage::is_id),
+            ("usiok", EngineMessage::is_usiok),
+            ("readyok", EngineMessage::is_readyok),
+            ("bestmove", EngineMessage::is_bestmove),
+            ("checkmate", EngineMessage::is_checkmate),
+            ("copyprotection", EngineMessage::is_copyprotection),
+            ("registration", EngineMessage::is_registration),
+            ("option", EngineMessage::is_option),
+            ("info", EngineMessage::is_info),
+            ("unknown", EngineMessage::is_unknown),
+        ];
+        let script = "id author tofutofu\nusiok\nreadyok\nbestmove resign\ncopyprotection ok\n\
+            registration error\noption name A type button\ninfo nodes 1\nyoho\n";
+        let mut msgs: Vec<EngineMessage> = EngineMessageStream::new(script).collect();
+        msgs.push(EngineMessage::CheckMate(CheckMateParams::NoMate));
+        assert_eq!(msgs.len(), engine_predicates.len());
+        for msg in &msgs {
+            for (kind, is) in &engine_predicates {
+                assert_eq!(is(msg), msg.kind() == *kind, "{msg:?}");
+            }
+        }
+
+        let EngineMessage::Info(params) = EngineMessage::parse(
+            "info depth 1 seldepth 2 time 3 nodes 4 multipv 1 score cp 5 currmove 7g7f \
+             currmovenumber 1 hashfull 6 nps 7 tbhits 8 sbhits 9 cpuload 10 refutation 7g7f \
+             currline 7g7f pv 7g7f string done\n",
+        )
+        .unwrap() else {
+            panic!("expected info");
+        };
+        assert_eq!(params.len(), 17);
+        for param in &params {
+            assert!(param.to_string().starts_with(param.kind()), "{param:?}");
+        }
+    }
+
+    #[test]
+    fn test_is_search_info() {
+        let progress = EngineMessage::parse("info string loading eval\n").unwrap();
+        assert!(progress.is_info());
+        assert!(!progress.is_search_info());
+
+        let search = EngineMessage::parse("info depth 1 seldepth 1 score cp 20 pv 7g7f\n").unwrap();
+        assert!(search.is_search_info());
+
+        assert!(!EngineMessage::ReadyOk.is_search_info());
+    }
+
+    //
+    // prefix parsing
+    //
+
+    #[test]
+    fn test_parse_prefix() {
+        let input = "usi\nisready\n";
+        let (msg, n) = GuiMessage::parse_prefix(input).unwrap();
+        assert_eq!(msg, GuiMessage::Usi);
+        assert_eq!(n, 4);
+        let (msg, m) = GuiMessage::parse_prefix(&input[n..]).unwrap();
+        assert_eq!(msg, GuiMessage::IsReady);
+        assert_eq!(n + m, input.len());
+
+        // consume a whole buffer message by message
+        let input = "\nusiok  \r\nyoho readyok\ndebug xyz\r\ngameover\ninfo depth 1\n";
+        let mut rest = input;
+        let mut msgs = Vec::new();
+        while !rest.is_empty() {
+            let (msg, n) = EngineMessage::parse_prefix(rest).unwrap();
+            assert!(n > 0);
+            msgs.push(msg);
+            rest = &rest[n..];
+        }
+        assert_eq!(
+            msgs,
+            vec![
+                EngineMessage::UsiOk,
+                EngineMessage::Unknown(s("yoho ")),
+                EngineMessage::ReadyOk,
+                EngineMessage::Unknown(s("debug xyz\r\n")),
+                EngineMessage::Unknown(s("gameover")),
+                EngineMessage::Info(vec![InfoParam::Depth(1)]),
+            ]
+        );
+
+        // an incomplete message is not consumed
+        assert_eq!(
+            EngineMessage::parse_prefix("readyok\nbestmo"),
+            Ok((EngineMessage::ReadyOk, 8))
+        );
+        assert_eq!(
+            EngineMessage::parse_prefix("bestmo"),
+            Err(ParseError::MissingNewline {
+                line: "bestmo".to_string()
+            })
+        );
+    }
+
+    //
+    // analysis params
+    //
+
+    #[test]
+    fn test_go_describe() {
+        let GuiMessage::Go(params) =
+            GuiMessage::parse("go btime 300000 wtime 290500 binc 10000 winc 10000 depth 20\n")
+                .unwrap()
+        else {
+            panic!("expected go");
+        };
+        assert_eq!(
+            params.describe(),
+            "Black 5:00 + 10s, White 4:50 + 10s, depth ≤ 20"
+        );
+        let params = EngineParams::new().ponder().btime(0).wtime(0).byoyomi(1500);
+        assert_eq!(
+            params.describe(),
+            "ponder, Black 0:00, White 0:00, byoyomi 1.5s"
+        );
+        assert_eq!(
+            EngineParams::new().mate(MateParam::Infinite).describe(),
+            "mate search"
+        );
+        assert_eq!(EngineParams::new().describe(), "");
+    }
+
+    #[test]
+    fn test_go_clamp_times() {
+        let GuiMessage::Go(params) =
+            GuiMessage::parse("go btime 999999999 wtime 0 byoyomi 10000 mate 86400000\n").unwrap()
+        else {
+            panic!("expected go");
+        };
+        let max = Duration::from_secs(600);
+        let params = params.clamp_times(max);
+        assert_eq!(params.btime_ms(), Some(600_000));
+        assert_eq!(params.wtime_ms(), Some(0));
+        assert_eq!(params.byoyomi_ms(), Some(10_000));
+        assert_eq!(params.binc_ms(), None);
+        assert_eq!(
+            GuiMessage::Go(params).to_usi_line(),
+            "go btime 600000 wtime 0 byoyomi 10000 mate 600000\n"
+        );
+    }
+
+    #[test]
+    fn test_go_time_forfeit() {
+        let GuiMessage::Go(params) = GuiMessage::parse("go btime 0 wtime 0 byoyomi 0\n").unwrap()
+        else {
+            panic!("expected go");
+        };
+        assert!(params.is_time_forfeit(Color::Black));
+        assert!(params.is_time_forfeit(Color::White));
+
+        let GuiMessage::Go(params) =
+            GuiMessage::parse("go btime 0 wtime 0 byoyomi 10000\n").unwrap()
+        else {
+            panic!("expected go");
+        };
+        assert!(!params.is_time_forfeit(Color::Black));
+        assert!(!params.is_time_forfeit(Color::White));
+
+        let params = EngineParams::new().btime(0).wtime(0).binc(0).winc(2000);
+        assert!(params.is_time_forfeit(Color::Black));
+        assert!(!params.is_time_forfeit(Color::White));
+
+        assert!(!EngineParams::new().btime(1).is_time_forfeit(Color::Black));
+        assert!(!EngineParams::new().infinite().is_time_forfeit(Color::Black));
+    }
+
+    #[test]
+    fn test_strip_time() {
+        let full = EngineParams::new()
+            .ponder()
+            .btime(60000)
+            .wtime(50000)
+            .binc(1000)
+            .winc(1000)
+            .byoyomi(0)
+            .movestogo(40)
+            .depth(20)
+            .nodes(1000000)
+            .movetime(5000)
+            .mate(MateParam::Infinite)
+            .searchmoves_from_strs(&["7g7f", "2g2f"])
+            .unwrap();
+        assert!(!full.analysis_only());
+
+        let stripped = full.clone().strip_time();
+        assert!(stripped.analysis_only());
+        assert_eq!(
+            stripped.to_string(),
+            " ponder depth 20 nodes 1000000 mate infinite movetime 5000 searchmoves 7g7f 2g2f"
+        );
+        assert_eq!(stripped.clone().strip_time(), stripped);
+
+        assert!(EngineParams::new().depth(20).analysis_only());
+        assert!(!EngineParams::new().movestogo(1).analysis_only());
+        assert!(EngineParams::new().analysis_only());
+    }
+
+    //
+    // go formats
+    //
+
+    #[test]
+    fn test_go_formats() {
+        let params = EngineParams::new()
+            .ponder()
+            .btime(60000)
+            .wtime(50000)
+            .binc(1000)
+            .winc(2000)
+            .depth(12)
+            .searchmoves(vec!["7g7f".parse().unwrap(), "2g2f".parse().unwrap()]);
+
+        let standard = params.format_go(GoFormat::Standard);
+        let uci = params.format_go(GoFormat::Uci);
+        assert_eq!(
+            standard,
+            " ponder btime 60000 wtime 50000 binc 1000 winc 2000 depth 12 searchmoves 7g7f 2g2f"
+        );
+        assert_eq!(
+            uci,
+            " searchmoves 7g7f 2g2f ponder wtime 50000 btime 60000 winc 2000 binc 1000 depth 12"
+        );
+        assert_eq!(params.to_string(), standard);
+
+        for line in [standard, uci] {
+            let msg = GuiMessage::parse(format!("go{}\n", line)).unwrap();
+            assert_eq!(msg, GuiMessage::Go(params.clone()));
+        }
+    }
+
+    #[test]
+    fn test_searchmoves_from_strs() {
+        let params = EngineParams::new()
+            .searchmoves_from_strs(&["7g7f", "P*5e"])
+            .unwrap();
+        assert_eq!(
+            params,
+            EngineParams::new().searchmoves(vec!["7g7f".parse().unwrap(), "P*5e".parse().unwrap()])
+        );
+
+        let err = EngineParams::new()
+            .searchmoves_from_strs(&["7g7f", "oops", "P*5"])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            InvalidMoves(vec!["oops".to_string(), "P*5".to_string()])
+        );
+        assert_eq!(err.to_string(), "invalid moves: oops P*5");
+    }
+
+    //
+    // info string
+    //
+
+    #[test]
+    fn test_info_string_is_terminal() {
+        let msg = EngineMessage::parse("info depth 5 string nps 12345 pv 7g7f\n").unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![
+                InfoParam::Depth(5),
+                InfoParam::String(s("nps 12345 pv 7g7f"))
+            ])
+        );
+        let msg = EngineMessage::parse("info string depth 3 string x\n").unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![InfoParam::String(s("depth 3 string x"))])
+        );
+    }
+
+    #[test]
+    fn test_info_string_newlines() {
+        let param = InfoParam::String("line1\nline2\r\nline3".into());
+        let out = param.to_string();
+        assert!(!out.contains(['\n', '\r']));
+        assert_eq!(out, "string line1 line2  line3");
+
+        let msg = EngineMessage::Info(vec![param]);
+        let line = msg.to_string();
+        assert_eq!(line.lines().count(), 1);
+        assert!(
+            EngineMessage::parse(format!("{}\n", line))
+                .unwrap()
+                .is_info()
+        );
+    }
+
+    //
+    // both directions
+    //
+
+    #[test]
+    fn test_parse_both() {
+        assert_eq!(parse_both("usiok\n"), (None, Some(EngineMessage::UsiOk)));
+        assert_eq!(parse_both("isready\n"), (Some(GuiMessage::IsReady), None));
+        assert_eq!(parse_both("yoho hello\n"), (None, None));
+        assert_eq!(parse_both(""), (None, None));
+    }
+
+    //
+    // nps consistency
+    //
+
+    #[test]
+    fn test_info_nps_consistency() {
+        let msg = EngineMessage::parse("info time 1000 nodes 100000 nps 100000\n").unwrap();
+        let ratio = msg.info_nps_consistency().unwrap();
+        assert!((ratio - 1.0).abs() < 1e-9);
+
+        let msg = EngineMessage::parse("info time 500 nodes 100000 nps 100000\n").unwrap();
+        let ratio = msg.info_nps_consistency().unwrap();
+        assert!((ratio - 0.5).abs() < 1e-9);
+
+        let msg = EngineMessage::parse("info nodes 100000 nps 100000\n").unwrap();
+        assert_eq!(msg.info_nps_consistency(), None);
+        let msg = EngineMessage::parse("info time 0 nodes 100000 nps 100000\n").unwrap();
+        assert_eq!(msg.info_nps_consistency(), None);
+        assert_eq!(EngineMessage::UsiOk.info_nps_consistency(), None);
+    }
+
+    //
+    // infallible parsing
+    //
+
+    #[test]
+    fn test_from_line() {
+        assert_eq!(GuiMessage::from_line("isready"), GuiMessage::IsReady);
+        assert_eq!(GuiMessage::from_line("isready\r\n"), GuiMessage::IsReady);
+        assert_eq!(
+            GuiMessage::from("go depth 3"),
+            GuiMessage::Go(EngineParams::new().depth(3))
+        );
+        assert!(GuiMessage::from_line("hello world").is_unknown());
+        assert!(GuiMessage::from_line("").is_unknown());
+
+        assert_eq!(EngineMessage::from_line("readyok"), EngineMessage::ReadyOk);
+        assert!(EngineMessage::from("oops").is_unknown());
+    }
+
+    //
+    // multi-word combo values
+    //
+
+    #[test]
+    fn test_combo_option_with_spaces() {
+        let input = "option name Style type combo default Very Solid var Very Solid var Wild\n";
+        let msg = EngineMessage::parse(input).unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Option(OptionParam::Combo {
+                name: "Style".to_string(),
+                default: Some("Very Solid".to_string()),
+                vars: vec!["Very Solid".to_string(), "Wild".to_string()],
+            })
+        );
+        assert_eq!(format!("{}\n", msg), input);
+
+        // words that merely start with a keyword are part of the value
+        let msg = EngineMessage::parse(
+            "option name V type combo default variant A var variant A var defaults\n",
+        )
+        .unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Option(OptionParam::Combo {
+                name: "V".to_string(),
+                default: Some("variant A".to_string()),
+                vars: vec!["variant A".to_string(), "defaults".to_string()],
+            })
+        );
+    }
+
+    //
+    // info summaries
+    //
+
+    #[test]
+    fn test_info_summarize() {
+        let input =
+            "info depth 10 score cp 30 pv 7g7f 3c3d 2g2f 8c8d 2f2e 8d8e 6i7h 4a3b 2e2d 2c2d\n";
+        let msg = EngineMessage::parse(input).unwrap();
+        assert_eq!(
+            msg.summarize(3),
+            "info depth 10 score cp 30 pv 7g7f 3c3d 2g2f ...(+7)"
+        );
+        assert_eq!(msg.summarize(10), msg.to_string());
+        assert_eq!(msg.to_string(), input.trim_end());
+
+        let msg = EngineMessage::parse("info currline 1 7g7f 3c3d refutation 2g2f 8c8d\n").unwrap();
+        assert_eq!(
+            msg.summarize(0),
+            "info currline 1 ...(+2) refutation ...(+2)"
+        );
+        assert_eq!(EngineMessage::UsiOk.summarize(3), "usiok");
+    }
+
+    //
+    // move lists
+    //
+
+    #[test]
+    fn test_move_list_round_trip() {
+        let text = "7g7f 3c3d 2g2f 8c8d P*5e 2b3a+";
+        let moves: MoveList = text.parse().unwrap();
+        assert_eq!(moves.len(), 6);
+        assert_eq!(moves[4], "P*5e".parse::<Move>().unwrap());
+        assert_eq!(moves.to_string(), text);
+        assert_eq!(moves.to_string().parse::<MoveList>().unwrap(), moves);
+
+        let collected: MoveList = moves.iter().copied().collect();
+        assert_eq!(collected, moves);
+        assert_eq!(Vec::from(collected), moves.clone().into_vec());
+
+        assert!("".parse::<MoveList>().unwrap().is_empty());
+        assert!("7g7f oops".parse::<MoveList>().is_err());
+    }
+
+    #[test]
+    fn test_move_list_handicap_position() {
+        // two-piece handicap: white (the handicap giver) moves first
+        let input = "position sfen lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1 moves 6c6d 7g7f 8b4b\n";
+        let msg = GuiMessage::parse(input).unwrap();
+        let GuiMessage::Position {
+            moves: Some(ref moves),
+            ..
+        } = msg
+        else {
+            panic!("expected a position with moves");
+        };
+        assert_eq!(*moves, "6c6d 7g7f 8b4b".parse().unwrap());
+        assert_eq!(moves.to_string(), "6c6d 7g7f 8b4b");
+        assert_eq!(format!("{}\n", msg), input);
+    }
+
+    //
+    // coalesced info
+    //
+
+    #[test]
+    fn test_coalesce_info() {
+        let input = "info depth 10\ninfo score cp 20\ninfo pv 7g7f 3c3d\ninfo depth 11 nodes 100\ninfo pv 2g2f\nbestmove 2g2f\ninfo string bye\n";
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(input).coalesce_info().collect();
+        assert_eq!(msgs.len(), 4);
+        assert_eq!(
+            msgs[0],
+            EngineMessage::Info(vec![
+                InfoParam::Depth(10),
+                InfoParam::ScoreCp(20, ScoreBound::Exact),
+                InfoParam::Pv("7g7f 3c3d".parse().unwrap()),
+            ])
+        );
+        assert_eq!(msgs[1].to_string(), "info depth 11 nodes 100 pv 2g2f");
+        assert!(msgs[2].is_bestmove());
+        assert_eq!(msgs[3].to_string(), "info string bye");
+    }
+
+    //
+    // kind filter
+    //
+
+    #[test]
+    fn test_filter_kinds() {
+        let input = "id name Foo\nusiok\nreadyok\ninfo depth 1 score cp 5\ninfo string hello\nyoho\noption name Bar type button\nbestmove 7g7f ponder 3c3d\n";
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(input)
+            .filter_kinds(&["info", "bestmove"])
+            .collect();
+        let kinds: Vec<&str> = msgs.iter().map(|msg| msg.kind()).collect();
+        assert_eq!(kinds, ["info", "info", "bestmove"]);
+        assert_eq!(msgs[0].to_string(), "info depth 1 score cp 5");
+
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(input)
+            .filter_kinds(&["unknown"])
+            .collect();
+        assert_eq!(msgs, [EngineMessage::Unknown(s("yoho"))]);
+        assert_eq!(EngineMessageStream::new(input).filter_kinds(&[]).count(), 0);
+    }
+
+    //
+    // millisecond getters
+    //
+
+    #[test]
+    fn test_millisecond_getters() {
+        let GuiMessage::Go(params) = GuiMessage::parse(
+            "go btime 300000 wtime 299999 binc 0 winc 10000 byoyomi 5000 movetime 1234\n",
+        )
+        .unwrap() else {
+            panic!("expected go");
+        };
+        assert_eq!(params.btime_ms(), Some(300000));
+        assert_eq!(params.wtime_ms(), Some(299999));
+        assert_eq!(params.binc_ms(), Some(0));
+        assert_eq!(params.winc_ms(), Some(10000));
+        assert_eq!(params.byoyomi_ms(), Some(5000));
+        assert_eq!(params.movetime_ms(), Some(1234));
+        assert_eq!(EngineParams::new().btime_ms(), None);
+
+        // sub-millisecond durations are rounded, as in Display
+        let params = EngineParams::new().btime(Duration::from_micros(1500));
+        assert_eq!(params.btime_ms(), Some(2));
+        assert_eq!(params.to_string(), " btime 2");
+
+        let EngineMessage::Info(info) = EngineMessage::parse("info depth 3 time 1501\n").unwrap()
+        else {
+            panic!("expected info");
+        };
+        assert_eq!(info[0].time_ms(), None);
+        assert_eq!(info[1].time_ms(), Some(1501));
+    }
+
+    #[test]
+    fn test_currline_cpu_or_default() {
+        for input in ["info currline 2g2f\n", "info currline 1 2g2f\n"] {
+            let EngineMessage::Info(info) = EngineMessage::parse(input).unwrap() else {
+                panic!("expected info: {input}");
+            };
+            assert_eq!(info[0].cpu_or_default(), Some(1), "{input}");
+        }
+        let EngineMessage::Info(info) =
+            EngineMessage::parse("info depth 2 currline 3 2g2f\n").unwrap()
+        else {
+            panic!("expected info");
+        };
+        assert_eq!(info[0].cpu_or_default(), None);
+        assert_eq!(info[1].cpu_or_default(), Some(3));
+    }
+
+    //
+    // no move
+    //
+
+    #[test]
+    fn test_bestmove_no_move() {
+        let options = ParseOptions {
+            no_move_tokens: Cow::Borrowed(NO_MOVE_TOKENS),
+            ..ParseOptions::default()
+        };
+        for input in ["bestmove 0000\n", "bestmove (none)\n"] {
+            let msg = EngineMessage::parse_with(input, &options).unwrap();
+            assert_eq!(msg, EngineMessage::BestMove(BestMoveParams::None));
+            assert!(msg.is_bestmove());
+            assert_eq!(msg.to_string(), "bestmove 0000");
+
+            // not part of the strict protocol
+            assert!(EngineMessage::parse(input).unwrap().is_unknown());
+        }
+        // a ponder move after the no-move token is dropped
+        for input in [
+            "bestmove 0000 ponder 7g7f\n",
+            "bestmove (none) ponder 0000\n",
+        ] {
+            let msg = EngineMessage::parse_with(input, &options).unwrap();
+            assert_eq!(
+                msg,
+                EngineMessage::BestMove(BestMoveParams::None),
+                "{input}"
+            );
+        }
+        assert!(
+            EngineMessage::parse_with("bestmove 0000 ponder\n", &options)
+                .unwrap()
+                .is_unknown()
+        );
+
+        let options = ParseOptions {
+            no_move_tokens: vec![Cow::Owned("none".to_string())].into(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            EngineMessage::parse_with("bestmove none\n", &options).unwrap(),
+            EngineMessage::BestMove(BestMoveParams::None)
+        );
+        assert!(
+            EngineMessage::parse_with("bestmove 0000\n", &options)
+                .unwrap()
+                .is_unknown()
+        );
+        assert_eq!(
+            EngineMessage::parse_with("bestmove resign\n", &options).unwrap(),
+            EngineMessage::BestMove(BestMoveParams::Resign)
+        );
+    }
+
+    //
+    // position equality
+    //
+
+    #[test]
+    fn test_position_constructors() {
+        assert_eq!(GuiMessage::startpos().to_string(), "position startpos");
+        assert_eq!(
+            GuiMessage::startpos(),
+            GuiMessage::parse("position startpos\n").unwrap()
+        );
+
+        let moves: Vec<Move> = vec!["7g7f".parse().unwrap(), "3c3d".parse().unwrap()];
+        let msg = GuiMessage::startpos_with_moves(moves);
+        assert_eq!(msg.to_string(), "position startpos moves 7g7f 3c3d");
+        assert_eq!(
+            GuiMessage::startpos_with_moves(MoveList::new()),
+            GuiMessage::startpos()
+        );
+
+        let msg = GuiMessage::from_sfen(SFEN_STARTPOS).unwrap();
+        assert_eq!(msg.to_string(), format!("position sfen {}", SFEN_STARTPOS));
+        assert_eq!(msg.position_eq(&GuiMessage::startpos()), Ok(true));
+        let err = GuiMessage::from_sfen("lnsgkgsnl/1r5b1 b - 1").unwrap_err();
+        assert_eq!(err, ParseError::InvalidSfen(s("lnsgkgsnl/1r5b1 b - 1")));
+        assert_eq!(err.to_string(), "invalid SFEN \"lnsgkgsnl/1r5b1 b - 1\"");
+    }
+
+    #[test]
+    fn test_position_trace() {
+        let msg = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+        let trace = msg.position_trace().unwrap();
+        let moves: Vec<Move> = trace.iter().map(|(mv, _)| *mv).collect();
+        assert_eq!(moves, Vec::from("7g7f 3c3d".parse::<MoveList>().unwrap()));
+        assert_eq!(
+            trace[0].1,
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2"
+        );
+        assert_eq!(
+            trace[1].1,
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3"
+        );
+        assert_eq!(trace[1].1, msg.resulting_sfen().unwrap());
+
+        let msg = GuiMessage::parse("position startpos\n").unwrap();
+        assert_eq!(msg.position_trace(), Ok(vec![]));
+        let msg = GuiMessage::parse("position startpos moves 7g7f 7g7f\n").unwrap();
+        assert!(matches!(
+            msg.position_trace(),
+            Err(PositionError::IllegalMove { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_position_cache() {
+        let mut cache = PositionCache::new();
+        let short = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+        let long = GuiMessage::parse("position startpos moves 7g7f 3c3d 2g2f 8c8d\n").unwrap();
+        assert_eq!(cache.resulting_sfen(&short), short.resulting_sfen());
+        assert_eq!(cache.hits(), 0);
+        assert_eq!(cache.resulting_sfen(&long), long.resulting_sfen());
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.len(), 2);
+        // an exact repeat is a hit too, and adds nothing
+        assert_eq!(cache.resulting_sfen(&long), long.resulting_sfen());
+        assert_eq!((cache.hits(), cache.len()), (2, 2));
+
+        // a different branch only shares the start position
+        let other = GuiMessage::parse("position startpos moves 2g2f\n").unwrap();
+        assert_eq!(cache.resulting_sfen(&other), other.resulting_sfen());
+        assert_eq!(cache.hits(), 2);
+
+        // errors are reported with the index in the full move list
+        let illegal = GuiMessage::parse("position startpos moves 7g7f 3c3d 7f7f\n").unwrap();
+        assert_eq!(cache.resulting_sfen(&illegal), illegal.resulting_sfen());
+        assert!(matches!(
+            cache.resulting_sfen(&illegal),
+            Err(PositionError::IllegalMove { index: 2, .. })
+        ));
+
+        // the capacity is respected, by evicting the oldest entry
+        let mut cache = PositionCache::with_capacity(2);
+        for msg in [&short, &long, &other] {
+            cache.resulting_sfen(msg).unwrap();
+        }
+        assert_eq!((cache.hits(), cache.len()), (1, 2));
+        cache.resulting_sfen(&long).unwrap();
+        assert_eq!(cache.hits(), 2);
+        // `short` was evicted
+        cache.resulting_sfen(&short).unwrap();
+        assert_eq!((cache.hits(), cache.len()), (2, 2));
+    }
+
+    #[test]
+    fn test_position_eq() {
+        let startpos = GuiMessage::parse("position startpos\n").unwrap();
+        let literal = GuiMessage::parse(format!("position sfen {}\n", SFEN_STARTPOS)).unwrap();
+        assert_eq!(startpos.position_eq(&literal), Ok(true));
+
+        let a = GuiMessage::parse("position startpos moves 7g7f 3c3d\n").unwrap();
+        let b = GuiMessage::parse(
+            "position sfen lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 1\n",
+        )
+        .unwrap();
+        assert_eq!(a.position_eq(&b), Ok(true));
+
+        // same moves in a different order reach the same position
+        let c = GuiMessage::parse("position startpos moves 2g2f 3c3d 7g7f 8c8d\n").unwrap();
+        let d = GuiMessage::parse("position startpos moves 7g7f 8c8d 2g2f 3c3d\n").unwrap();
+        assert_eq!(c.position_eq(&d), Ok(true));
+
+        let e = GuiMessage::parse("position startpos moves 7g7f 8c8d\n").unwrap();
+        assert_eq!(a.position_eq(&e), Ok(false));
+        assert_eq!(startpos.position_eq(&a), Ok(false));
+
+        let illegal = GuiMessage::parse("position startpos moves 7g7f 7g7f\n").unwrap();
+        assert!(matches!(
+            illegal.position_eq(&a),
+            Err(PositionError::IllegalMove { index: 1, .. })
+        ));
+        assert_eq!(
+            startpos.position_eq(&GuiMessage::IsReady),
+            Err(PositionError::NotAPosition)
+        );
+    }
+
+    //
+    // blank lines
+    //
+
+    #[test]
+    fn test_streams_skip_blank_lines() {
+        let input = "\nusi\n\n   \n\t\nisready\r\n\r\n\nquit\n\n";
+        let msgs: Vec<GuiMessage> = GuiMessageStream::new(input).collect();
+        assert_eq!(
+            msgs,
+            vec![GuiMessage::Usi, GuiMessage::IsReady, GuiMessage::Quit]
+        );
+
+        let input = "usiok\n\n\n  \nreadyok\n\nyoho\n\n";
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(input).collect();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0], EngineMessage::UsiOk);
+        assert_eq!(msgs[1], EngineMessage::ReadyOk);
+        assert!(msgs[2].is_unknown());
+    }
+
+    //
+    // round-trip properties
+    //
+    // A tiny deterministic generator, so that property tests need no extra dependency.
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            // xorshift64*
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn coin(&mut self) -> bool {
+            self.below(2) == 0
+        }
+
+        fn millis(&mut self) -> Duration {
+            Duration::from_millis(self.below(1 << 40))
+        }
+
+        fn usi_move(&mut self) -> Move {
+            let file = |rng: &mut Rng| (b'1' + rng.below(9) as u8) as char;
+            let rank = |rng: &mut Rng| (b'a' + rng.below(9) as u8) as char;
+            let text = if self.below(4) == 0 {
+                let piece = ['P', 'L', 'N', 'S', 'G', 'B', 'R'][self.below(7) as usize];
+                format!("{}*{}{}", piece, file(self), rank(self))
+            } else {
+                let promotion = if self.coin() { "+" } else { "" };
+                format!(
+                    "{}{}{}{}{}",
+                    file(self),
+                    rank(self),
+                    file(self),
+                    rank(self),
+                    promotion
+                )
+            };
+            text.parse().unwrap()
+        }
+
+        fn usi_moves(&mut self, max: u64) -> Vec<Move> {
+            (0..self.below(max + 1)).map(|_| self.usi_move()).collect()
+        }
+    }
+
+    fn random_engine_params(rng: &mut Rng) -> EngineParams {
+        let mut params = EngineParams::new();
+        if rng.coin() {
+            params = params.ponder();
+        }
+        if rng.coin() {
+            params = params.btime(rng.millis());
+        }
+        if rng.coin() {
+            params = params.wtime(rng.millis());
+        }
+        // increments and byoyomi are not used together
+        if rng.coin() {
+            params = params.byoyomi(rng.millis());
+        } else {
+            if rng.coin() {
+                params = params.binc(Duration::from_millis(rng.below(3) * 1000));
+            }
+            if rng.coin() {
+                params = params.winc(Duration::from_millis(rng.below(3) * 1000));
+            }
+        }
+        if rng.coin() {
+            params = params.movestogo(rng.next() as u16);
+        }
+        if rng.coin() {
+            params = params.depth(rng.next() as u16);
+        }
+        if rng.coin() {
+            params = params.nodes(rng.next() as u32);
+        }
+        if rng.coin() {
+            params = params.mate(if rng.coin() {
+                MateParam::Timeout(rng.millis())
+            } else {
+                MateParam::Infinite
+            });
+        }
+        if rng.coin() {
+            params = params.movetime(rng.millis());
+        }
+        if rng.coin() {
+            params = params.infinite();
+        }
+        if rng.coin() {
+            params = params.searchmoves(rng.usi_moves(5));
+        }
+        params
+    }
+
+    #[test]
+    fn test_engine_params_round_trip() {
+        let mut rng = Rng(0x5eed_1234_abcd_0001);
+        for _ in 0..2000 {
+            let msg = GuiMessage::Go(random_engine_params(&mut rng));
+            let line = format!("{}\n", msg);
+            assert_eq!(GuiMessage::parse(&line).unwrap(), msg, "{line}");
+        }
+
+        let options = ParseOptions {
+            mate_in_moves: true,
+            ..ParseOptions::default()
+        };
+        for _ in 0..200 {
+            let n = rng.next() as u16;
+            let msg = GuiMessage::Go(random_engine_params(&mut rng).mate(MateParam::Moves(n)));
+            let line = format!("{}\n", msg);
+            assert_eq!(
+                GuiMessage::parse_with(&line, &options).unwrap(),
+                msg,
+                "{line}"
+            );
+        }
+
+        // an empty searchmoves list is not sent
+        let params = EngineParams::new().depth(1).searchmoves(vec![]);
+        assert_eq!(params, EngineParams::new().depth(1));
+    }
+
+    fn random_info_param(rng: &mut Rng) -> InfoParam {
+        let bound = |rng: &mut Rng| match rng.below(3) {
+            0 => ScoreBound::Exact,
+            1 => ScoreBound::Lower,
+            _ => ScoreBound::Upper,
+        };
+        let nonempty = |rng: &mut Rng| {
+            let mut moves = rng.usi_moves(4);
+            moves.push(rng.usi_move());
+            MoveList::from(moves)
+        };
+        match rng.below(17) {
+            0 => InfoParam::Depth(rng.next() as u16),
+            1 => InfoParam::SelDepth(rng.next() as u16),
+            2 => InfoParam::Time(rng.millis()),
+            3 => InfoParam::Nodes(rng.next()),
+            4 => InfoParam::Pv(nonempty(rng)),
+            5 => InfoParam::MultiPv(rng.next() as u16),
+            6 => InfoParam::ScoreCp(rng.next() as i32, bound(rng)),
+            7 => match rng.below(3) {
+                0 => InfoParam::ScoreMate(None, ScoreBound::MatePlus),
+                1 => InfoParam::ScoreMate(None, ScoreBound::MateMin),
+                _ => InfoParam::ScoreMate(Some(rng.next() as i32), bound(rng)),
+            },
+            8 => InfoParam::CurrMove(rng.usi_move()),
+            9 => InfoParam::CurrMoveNumber(rng.next() as u16),
+            10 => InfoParam::HashFull(rng.below(1001) as u16),
+            11 => InfoParam::Nps(rng.next()),
+            12 => InfoParam::TbHits(rng.next()),
+            13 => InfoParam::SbHits(rng.next()),
+            14 => InfoParam::CpuLoad(rng.below(1001) as u16),
+            15 => InfoParam::Refutation(nonempty(rng)),
+            _ => InfoParam::CurrLine {
+                cpu_nr: rng.coin().then(|| rng.next() as u16),
+                line: nonempty(rng),
+            },
+        }
+    }
+
+    #[test]
+    fn test_info_round_trip() {
+        let words = ["hello", "depth", "pv", "7g7f", "-", "+", "(none)", "multi"];
+        let mut rng = Rng(0x1f0_cafe_f00d_0002);
+        for _ in 0..2000 {
+            let mut params: Vec<InfoParam> = (0..=rng.below(6))
+                .map(|_| random_info_param(&mut rng))
+                .collect();
+            // an info string takes the rest of the line, so it can only come last
+            if rng.below(4) == 0 {
+                let text: Vec<&str> = (0..=rng.below(4))
+                    .map(|_| words[rng.below(words.len() as u64) as usize])
+                    .collect();
+                params.push(InfoParam::String(text.join(" ")));
+            }
+            let msg = EngineMessage::Info(params);
+            let line = format!("{}\n", msg);
+            let parsed = EngineMessage::parse(&line).unwrap();
+            assert!(parsed.semantically_eq(&msg), "{line}\n{parsed:?}\n{msg:?}");
+        }
+    }
+
+    //
+    // setoption without name
+    //
+
+    #[test]
+    fn test_gameover_synonyms() {
+        let input = "gameover won\n";
+        assert!(GuiMessage::parse(input).unwrap().is_unknown());
+
+        let options = ParseOptions {
+            gameover_synonyms: Cow::Borrowed(GAMEOVER_SYNONYMS),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            GuiMessage::parse_with(input, &options).unwrap(),
+            GuiMessage::GameOver(GameStatus::Win)
+        );
+        assert_eq!(
+            GuiMessage::parse_with("gameover sennichite \n", &options).unwrap(),
+            GuiMessage::GameOver(GameStatus::Draw)
+        );
+        // the protocol words are still accepted, anything else is still rejected
+        assert_eq!(
+            GuiMessage::parse_with("gameover lose\n", &options).unwrap(),
+            GuiMessage::GameOver(GameStatus::Lose)
+        );
+        assert!(
+            GuiMessage::parse_with("gameover sente\n", &options)
+                .unwrap()
+                .is_unknown()
+        );
+        assert!(
+            GuiMessage::parse_with("gameover won twice\n", &options)
+                .unwrap()
+                .is_unknown()
+        );
+
+        // a custom table built at runtime, for an engine playing gote
+        let (sente, gote) = (String::from("sente"), String::from("gote"));
+        let options = ParseOptions {
+            gameover_synonyms: vec![
+                (sente.into(), GameStatus::Lose),
+                (gote.into(), GameStatus::Win),
+            ]
+            .into(),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            GuiMessage::parse_with("gameover gote\n", &options).unwrap(),
+            GuiMessage::GameOver(GameStatus::Win)
+        );
+    }
+
+    #[test]
+    fn test_info_key_value_option() {
+        assert!(EngineMessage::parse("info depth=5\n").unwrap().is_unknown());
+
+        let options = ParseOptions {
+            info_key_value: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            EngineMessage::parse_with("info depth=5\n", &options).unwrap(),
+            EngineMessage::Info(vec![InfoParam::Depth(5)])
+        );
+        assert_eq!(
+            EngineMessage::parse_with("info depth=5 score=cp:120 pv 7g7f\n", &options).unwrap(),
+            EngineMessage::Info(vec![
+                InfoParam::Depth(5),
+                InfoParam::ScoreCp(120, ScoreBound::Exact),
+                InfoParam::Pv("7g7f".parse().unwrap()),
+            ])
+        );
+        // the free text of `info string` is left alone, other messages too
+        assert_eq!(
+            EngineMessage::parse_with("info nodes=10 string a=b c:d\n", &options).unwrap(),
+            EngineMessage::Info(vec![InfoParam::Nodes(10), InfoParam::String(s("a=b c:d"))])
+        );
+        assert_eq!(
+            EngineMessage::parse_with("id name a=b\n", &options).unwrap(),
+            EngineMessage::Id(IdParams::Name(s("a=b")))
+        );
+    }
+
+    #[test]
+    fn test_info_percent_suffix_option() {
+        assert!(
+            EngineMessage::parse("info hashfull 45%\n")
+                .unwrap()
+                .is_unknown()
+        );
+
+        let options = ParseOptions {
+            info_percent_suffix: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            EngineMessage::parse_with("info hashfull 45%\n", &options).unwrap(),
+            EngineMessage::Info(vec![InfoParam::HashFull(45)])
+        );
+        assert_eq!(
+            EngineMessage::parse_with("info depth 3 cpuload 98% hashfull 5\n", &options).unwrap(),
+            EngineMessage::Info(vec![
+                InfoParam::Depth(3),
+                InfoParam::CpuLoad(98),
+                InfoParam::HashFull(5)
+            ])
+        );
+        // only after hashfull and cpuload, and not in the string
+        assert!(
+            EngineMessage::parse_with("info depth 3%\n", &options)
+                .unwrap()
+                .is_unknown()
+        );
+        assert_eq!(
+            EngineMessage::parse_with("info string hashfull 45%\n", &options).unwrap(),
+            EngineMessage::Info(vec![InfoParam::String(s("hashfull 45%"))])
+        );
+    }
+
+    #[test]
+    fn test_setoption_without_name() {
+        let input = "setoption USI_Hash value 128\n";
+        assert!(GuiMessage::parse(input).unwrap().is_unknown());
+
+        let options = ParseOptions {
+            setoption_without_name: true,
+            ..ParseOptions::default()
+        };
+        let expected = GuiMessage::SetOption {
+            name: s("USI_Hash"),
+            value: Some(s("128")),
+        };
+        assert_eq!(GuiMessage::parse_with(input, &options).unwrap(), expected);
+        assert_eq!(
+            GuiMessage::parse_with("setoption name USI_Hash value 128\n", &options).unwrap(),
+            expected
+        );
+        assert_eq!(
+            GuiMessage::parse_with("setoption USI_Ponder\n", &options).unwrap(),
+            GuiMessage::SetOption {
+                name: s("USI_Ponder"),
+                value: None
+            }
+        );
+
+        let options = ParseOptions {
+            case_insensitive: true,
+            ..options
+        };
+        assert_eq!(
+            GuiMessage::parse_with("SetOption USI_Hash value 128\n", &options).unwrap(),
+            expected
+        );
+    }
+
+    //
+    // ordering
+    //
+
+    #[test]
+    fn test_message_ordering() {
+        let mut msgs: Vec<EngineMessage> = EngineMessageStream::new(
+        "info depth 2 pv 2g2f\nbestmove 7g7f\nusiok\ninfo depth 1\nbestmove P*5e\nid name x\nbestmove resign\nyoho\ninfo depth 2 pv 2g2f 8c8d\nreadyok\n",
+    )
+    .collect();
+        msgs.sort();
+        let sorted: Vec<String> = msgs.iter().map(|msg| msg.to_string()).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "id name x",
+                "usiok",
+                "readyok",
+                "bestmove P*5e",
+                "bestmove 7g7f",
+                "bestmove resign",
+                "info depth 1",
+                "info depth 2 pv 2g2f",
+                "info depth 2 pv 2g2f 8c8d",
+                "UNKNOWN \"yoho\"",
+            ]
+        );
+
+        // sorting is stable under reordering of the input
+        let mut reversed = msgs.clone();
+        reversed.reverse();
+        reversed.sort();
+        assert_eq!(reversed, msgs);
+
+        let set: std::collections::BTreeSet<GuiMessage> = GuiMessageStream::new(
+            "isready\nusi\ngo depth 3\nisready\ngo depth 2\nposition startpos\n",
+        )
+        .collect();
+        let set: Vec<String> = set.iter().map(|msg| msg.to_string()).collect();
+        assert_eq!(
+            set,
+            vec![
+                "usi",
+                "isready",
+                "position startpos",
+                "go depth 2",
+                "go depth 3"
+            ]
+        );
+    }
+
+    //
+    // sfen and moves boundary
+    //
+
+    #[test]
+    fn test_position_sfen_moves_boundary() {
+        let handicap = "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1";
+        let msg = GuiMessage::parse(format!("position sfen {handicap} moves 6c6d\n")).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Position {
+                sfen: Some(s(handicap)),
+                moves: Some("6c6d".parse().unwrap()),
+            }
+        );
+        assert_eq!(
+            msg.resulting_sfen().unwrap(),
+            "lnsgkgsnl/9/ppp1ppppp/3p5/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 2"
+        );
+
+        // without the move number, "moves" is not mistaken for a SFEN field
+        let three_fields = "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w -";
+        let msg =
+            GuiMessage::parse(format!("position sfen {three_fields} moves 6c6d 7g7f\n")).unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Position {
+                sfen: Some(s(three_fields)),
+                moves: Some("6c6d 7g7f".parse().unwrap()),
+            }
+        );
+
+        // a multi-digit move number next to "moves"
+        let msg =
+            GuiMessage::parse(format!("position sfen {three_fields} 124 moves 6c6d\n")).unwrap();
+        let GuiMessage::Position { sfen, moves } = msg else {
+            panic!("expected position");
+        };
+        assert!(sfen.unwrap().ends_with(" w - 124"));
+        assert_eq!(moves.unwrap().len(), 1);
+    }
+
+    //
+    // clock
+    //
+
+    #[test]
+    fn test_clock_increment() {
+        let mut clock = UsiClock::new();
+        let go = GuiMessage::parse("go btime 10000 wtime 10000 binc 1000 winc 2000\n").unwrap();
+        let GuiMessage::Go(params) = go else {
+            panic!("expected go");
+        };
+        clock.apply_go(&params);
+        assert_eq!(clock.byoyomi, Duration::ZERO);
+
+        assert!(clock.charge(Color::Black, Duration::from_millis(3000)));
+        assert!(clock.charge(Color::White, Duration::from_millis(500)));
+        assert!(clock.charge(Color::Black, Duration::from_millis(8000)));
+        assert_eq!(clock.time(Color::Black), Duration::from_millis(1000));
+        assert_eq!(clock.time(Color::White), Duration::from_millis(11500));
+        assert_eq!(
+            GuiMessage::Go(clock.go_params()).to_string(),
+            "go btime 1000 wtime 11500 binc 1000 winc 2000"
+        );
+
+        // no byoyomi to fall back on
+        assert!(!clock.charge(Color::Black, Duration::from_millis(1001)));
+        assert_eq!(clock.btime, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clock_byoyomi() {
+        let mut clock = UsiClock::new();
+        clock.apply_go(&EngineParams::new().btime(2000).wtime(0).byoyomi(1000));
+
+        assert!(clock.charge(Color::Black, Duration::from_millis(1500)));
+        assert_eq!(clock.btime, Duration::from_millis(500));
+        // the main time runs out during the move, the rest comes out of the byoyomi
+        assert!(clock.charge(Color::Black, Duration::from_millis(1500)));
+        assert_eq!(clock.btime, Duration::ZERO);
+        // byoyomi starts afresh each move
+        assert!(clock.charge(Color::Black, Duration::from_millis(1000)));
+        assert!(clock.charge(Color::White, Duration::from_millis(900)));
+        assert_eq!(
+            GuiMessage::Go(clock.go_params()).to_string(),
+            "go btime 0 wtime 0 byoyomi 1000"
+        );
+        assert!(!clock.charge(Color::White, Duration::from_millis(1001)));
+    }
+
+    #[test]
+    fn test_info_numbers_saturate() {
+        let msg =
+            EngineMessage::parse("info depth 70000 seldepth 99999999999999999999 multipv 65536\n");
+        assert_eq!(
+            msg.unwrap(),
+            EngineMessage::Info(vec![
+                InfoParam::Depth(u16::MAX),
+                InfoParam::SelDepth(u16::MAX),
+                InfoParam::MultiPv(u16::MAX)
+            ])
+        );
+        let msg = EngineMessage::parse("info score cp -9999999999 nodes 99999999999999999999\n");
+        assert_eq!(
+            msg.unwrap(),
+            EngineMessage::Info(vec![
+                InfoParam::ScoreCp(i32::MIN, ScoreBound::Exact),
+                InfoParam::Nodes(u64::MAX)
+            ])
+        );
+        let msg = GuiMessage::parse("go depth 123456 btime 99999999999999999999\n").unwrap();
+        assert_eq!(
+            msg,
+            GuiMessage::Go(EngineParams::new().depth(u16::MAX).btime(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_move_squares() {
+        let bestmove = |line: &str| {
+            let EngineMessage::BestMove(params) = EngineMessage::parse(line).unwrap() else {
+                panic!("expected bestmove");
+            };
+            params
+        };
+        assert_eq!(
+            bestmove("bestmove 8h2b+ ponder 3a2b\n").squares(),
+            Some((Some(Square::H8), Square::B2))
+        );
+        assert_eq!(
+            bestmove("bestmove G*5b\n").squares(),
+            Some((None, Square::B5))
+        );
+        assert_eq!(bestmove("bestmove resign\n").squares(), None);
+        assert_eq!(bestmove("bestmove win\n").squares(), None);
+
+        let EngineMessage::Info(params) =
+            EngineMessage::parse("info depth 2 currmove P*5e\n").unwrap()
+        else {
+            panic!("expected info");
+        };
+        assert_eq!(params[0].currmove_squares(), None);
+        assert_eq!(params[1].currmove_squares(), Some((None, Square::E5)));
+    }
+
+    #[test]
+    fn test_info_empty_pv() {
+        let msg = EngineMessage::parse("info depth 1 pv\n").unwrap();
+        assert_eq!(msg, EngineMessage::Info(vec![InfoParam::Depth(1)]));
+        let msg = EngineMessage::parse("info depth 1 pv score cp 3\n").unwrap();
+        assert_eq!(
+            msg,
+            EngineMessage::Info(vec![
+                InfoParam::Depth(1),
+                InfoParam::ScoreCp(3, ScoreBound::Exact)
+            ])
+        );
+        let msg = EngineMessage::Info(vec![InfoParam::Depth(1), InfoParam::Pv(MoveList::new())]);
+        assert_eq!(msg.to_string(), "info depth 1");
+        let msg = EngineMessage::parse("info pv\n").unwrap();
+        assert_eq!(msg, EngineMessage::Info(vec![]));
+        assert_eq!(msg.to_string(), "info");
+        // `pv` must still be a separate word
+        assert!(
+            EngineMessage::parse("info depth 1 pvx\n")
+                .unwrap()
+                .is_unknown()
+        );
+    }
+
+    //
+    // multipv table
+    //
+
+    #[test]
+    fn test_pv_with_index() {
+        let msg = EngineMessage::parse("info multipv 3 pv 5g5f 4g4f\n").unwrap();
+        let expected: MoveList = "5g5f 4g4f".parse().unwrap();
+        assert_eq!(msg.pv_with_index(), Some((3, expected.as_slice())));
+
+        let msg = EngineMessage::parse("info depth 2 pv 7g7f\n").unwrap();
+        let (index, pv) = msg.pv_with_index().unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(pv, ["7g7f".parse::<Move>().unwrap()]);
+
+        let msg = EngineMessage::parse("info multipv 2 score cp 10\n").unwrap();
+        assert_eq!(msg.pv_with_index(), None);
+        assert_eq!(EngineMessage::UsiOk.pv_with_index(), None);
+    }
+
+    #[test]
+    fn test_current_move() {
+        let mv: Move = "2g2f".parse().unwrap();
+        let msg = EngineMessage::parse("info currmove 2g2f currmovenumber 1\n").unwrap();
+        assert_eq!(msg.current_move(), Some((mv, Some(1))));
+
+        // in either order
+        let msg = EngineMessage::parse("info currmovenumber 12 depth 5 currmove 2g2f\n").unwrap();
+        assert_eq!(msg.current_move(), Some((mv, Some(12))));
+
+        let msg = EngineMessage::parse("info depth 5 currmove 2g2f\n").unwrap();
+        assert_eq!(msg.current_move(), Some((mv, None)));
+
+        let msg = EngineMessage::parse("info currmovenumber 3\n").unwrap();
+        assert_eq!(msg.current_move(), None);
+        assert_eq!(EngineMessage::ReadyOk.current_move(), None);
+    }
+
+    #[test]
+    fn test_multipv_table() {
+        // the multipv example in the `EngineMessage::Info` doc comment, out of order
+        let input = "\
+info score cp -157 multipv 3 pv 5g5f 4g4f 4e3c+ 4c3c
+info score cp 156 multipv 1 pv P*5h 4g5g 5h5g 8b8f
+info nodes 120000 nps 116391 hashfull 104
+info score cp -99 multipv 2 pv 2d4d 3c4e 8h5e N*7f
+";
+        let mut table = MultiPvTable::new();
+        for msg in EngineMessageStream::new(input) {
+            table.update(&msg);
+        }
+        let ranked: Vec<(u16, String, Option<InfoParam>)> = table
+            .ranked()
+            .into_iter()
+            .map(|(n, line)| (n, line.pv.to_string(), line.score.clone()))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![
+                (
+                    1,
+                    s("P*5h 4g5g 5h5g 8b8f"),
+                    Some(InfoParam::ScoreCp(156, ScoreBound::Exact))
+                ),
+                (
+                    2,
+                    s("2d4d 3c4e 8h5e N*7f"),
+                    Some(InfoParam::ScoreCp(-99, ScoreBound::Exact))
+                ),
+                (
+                    3,
+                    s("5g5f 4g4f 4e3c+ 4c3c"),
+                    Some(InfoParam::ScoreCp(-157, ScoreBound::Exact))
+                ),
+            ]
+        );
+
+        // a later line replaces the earlier one with the same index
+        table
+            .update(&EngineMessage::parse("info depth 5 score cp 40 multipv 2 pv 7g7f\n").unwrap());
+        let line = table.get(2).unwrap();
+        assert_eq!(line.depth, Some(5));
+        assert_eq!(line.pv.to_string(), "7g7f");
+        assert_eq!(table.len(), 3);
+
+        table.update(&EngineMessage::parse("bestmove P*5h\n").unwrap());
+        assert!(table.ranked().is_empty());
+    }
+
+    //
+    // engine message reader
+    //
+
+    #[test]
+    fn test_reader_read_message() {
+        let input = "id name haitaka\r\n\nyoho\ninfo depth 3";
+        let mut reader = EngineMessageReader::new(input.as_bytes());
+        assert_eq!(
+            reader.read_message().unwrap(),
+            Some(EngineMessage::Id(IdParams::Name(s("haitaka"))))
+        );
+        // the blank line is skipped
+        assert_eq!(
+            reader.read_message().unwrap(),
+            Some(EngineMessage::Unknown(s("yoho")))
+        );
+        // the last line lacks a newline
+        assert_eq!(
+            reader.read_message().unwrap(),
+            Some(EngineMessage::Info(vec![InfoParam::Depth(3)]))
+        );
+    }
+
+    #[test]
+    fn test_reader_eof() {
+        let mut reader = EngineMessageReader::new("".as_bytes());
+        assert_eq!(reader.read_message().unwrap(), None);
+        let mut reader = EngineMessageReader::new("usiok\n\n".as_bytes());
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+        assert_eq!(reader.read_message().unwrap(), None);
+        assert_eq!(reader.read_message().unwrap(), None);
+    }
+
+    #[test]
+    fn test_reader_cr_line_endings() {
+        let mut reader = EngineMessageReader::new("usiok\rreadyok\r".as_bytes());
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::ReadyOk));
+        assert_eq!(reader.read_message().unwrap(), None);
+
+        // mixed line endings, with a blank line in between
+        let input = "usiok\r\rreadyok\r\nbestmove 7g7f\n";
+        let mut reader = EngineMessageReader::new(input.as_bytes());
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::ReadyOk));
+        assert!(reader.read_message().unwrap().unwrap().is_bestmove());
+        assert_eq!(reader.read_message().unwrap(), None);
+
+        // a line ending in `\r` is returned without reading ahead
+        struct Pending;
+        impl std::io::Read for Pending {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WouldBlock.into())
+            }
+        }
+        let input = std::io::Read::chain("usiok\r".as_bytes(), std::io::BufReader::new(Pending));
+        let mut reader = EngineMessageReader::new(input);
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+        assert!(reader.read_message().is_err());
+    }
+
+    #[test]
+    fn test_reader_line_too_long() {
+        let mut reader = EngineMessageReader::new("usiok\r\nreadyok\n".as_bytes()).max_line_len(5);
+        assert_eq!(reader.read_message().unwrap(), Some(EngineMessage::UsiOk));
+        let err = reader.read_message().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let input = "info string ".repeat(100);
+        let mut reader = EngineMessageReader::new(input.as_bytes()).max_line_len(1000);
+        assert!(reader.read_message().is_err());
+    }
+
+    //
+    // ponder state
+    //
+
+    #[test]
+    fn test_ponder_state_match() {
+        let mut state = PonderState::new();
+        let position = GuiMessage::parse("position startpos moves 7g7f 3c3d 2g2f 8c8d\n").unwrap();
+        assert!(!state.matches_position(&position));
+
+        state.observe(&EngineMessage::parse("bestmove 2g2f ponder 8c8d\n").unwrap());
+        assert_eq!(state.ponder(), Some("8c8d".parse().unwrap()));
+        assert!(state.matches_position(&position));
+        assert!(state.is_ponderhit(&"8c8d".parse().unwrap()));
+
+        // info messages do not affect the prediction
+        state.observe(&EngineMessage::parse("info depth 3\n").unwrap());
+        assert!(state.matches_position(&position));
+    }
+
+    #[test]
+    fn test_ponder_state_mismatch() {
+        let mut state = PonderState::new();
+        state.observe(&EngineMessage::parse("bestmove 2g2f ponder 8c8d\n").unwrap());
+        for input in [
+            "position startpos moves 7g7f 3c3d 2g2f 3a4b\n",
+            "position startpos moves 7g7f 3c3d 8c8d\n",
+            "position startpos\n",
+        ] {
+            let position = GuiMessage::parse(input).unwrap();
+            assert!(!state.matches_position(&position), "{input}");
+        }
+        assert!(!state.is_ponderhit(&"3a4b".parse().unwrap()));
+
+        // a bestmove without ponder move clears the prediction
+        state.observe(&EngineMessage::parse("bestmove 2g2f\n").unwrap());
+        assert_eq!(state.ponder(), None);
+        assert!(!state.is_ponderhit(&"8c8d".parse().unwrap()));
+    }
+
+    //
+    // game record
+    //
+
+    #[test]
+    fn test_game_record() {
+        let gui = |line: &str| UsiMessage::from(GuiMessage::parse(line).unwrap());
+        let engine = |line: &str| UsiMessage::from(EngineMessage::parse(line).unwrap());
+        let messages = [
+            gui("usinewgame\n"),
+            gui("position startpos moves 7g7f\n"),
+            gui("go btime 1000 wtime 1000 byoyomi 1000\n"),
+            engine("info depth 1 pv 3c3d\n"),
+            engine("bestmove 3c3d ponder 2g2f\n"),
+            gui("position startpos moves 7g7f 3c3d 2g2f\n"),
+            gui("go btime 1000 wtime 1000 byoyomi 1000\n"),
+            engine("bestmove 8c8d\n"),
+            gui("gameover lose\n"),
+        ];
+        let record = GameRecord::from_messages(&messages);
+        assert_eq!(record.moves().to_string(), "7g7f 3c3d 2g2f 8c8d");
+        assert_eq!(record.result(), Some(GameStatus::Lose));
+        assert_eq!(record.sfen(), None);
+        assert_eq!(
+            record.position().to_string(),
+            "position startpos moves 7g7f 3c3d 2g2f 8c8d"
+        );
+
+        // a new game starts a new record
+        let mut record = record;
+        record.update(&gui("usinewgame\n"));
+        assert_eq!(record, GameRecord::new());
+    }
+
+    #[test]
+    fn test_game_record_pondering() {
+        let gui = |line: &str| UsiMessage::from(GuiMessage::parse(line).unwrap());
+        let engine = |line: &str| UsiMessage::from(EngineMessage::parse(line).unwrap());
+        let mut record = GameRecord::from_messages(&[
+            gui("usinewgame\n"),
+            gui("position startpos moves 7g7f\n"),
+            gui("go btime 1000 wtime 1000 byoyomi 1000\n"),
+            engine("bestmove 3c3d ponder 2g2f\n"),
+            // the engine ponders on the predicted reply 2g2f
+            gui("position startpos moves 7g7f 3c3d 2g2f\n"),
+            gui("go ponder btime 1000 wtime 1000 byoyomi 1000\n"),
+        ]);
+        assert_eq!(record.moves().to_string(), "7g7f 3c3d");
+
+        // a ponder hit: the prediction was played
+        let mut hit = record.clone();
+        hit.update(&gui("ponderhit\n"));
+        assert_eq!(hit.moves().to_string(), "7g7f 3c3d 2g2f");
+        hit.update(&engine("bestmove 8c8d\n"));
+        assert_eq!(hit.moves().to_string(), "7g7f 3c3d 2g2f 8c8d");
+
+        // a ponder miss: the GUI stops the search and discards the bestmove
+        record.update(&gui("stop\n"));
+        record.update(&engine("bestmove 8c8d ponder 2f2e\n"));
+        assert_eq!(record.moves().to_string(), "7g7f 3c3d");
+
+        // the next position has the move that was actually played
+        record.update(&gui("position startpos moves 7g7f 3c3d 2h2f\n"));
+        record.update(&gui("go btime 1000 wtime 1000 byoyomi 1000\n"));
+        record.update(&engine("bestmove 8c8d\n"));
+        assert_eq!(record.moves().to_string(), "7g7f 3c3d 2h2f 8c8d");
+    }
+
+    #[test]
+    fn test_usi_message_conversions() {
+        let msg: UsiMessage = GuiMessage::parse("isready\n").unwrap().into();
+        assert_eq!(msg, UsiMessage::UsiGuiToEngine(GuiMessage::IsReady));
+        assert_eq!(msg.direction(), Some(Direction::GuiToEngine));
+
+        let msg: UsiMessage = EngineMessage::parse("readyok\n").unwrap().into();
+        assert_eq!(msg, UsiMessage::UsiEngineToGui(EngineMessage::ReadyOk));
+        assert_eq!(msg.direction(), Some(Direction::EngineToGui));
+
+        let msg = UsiMessage::from(GuiMessage::parse("yoho\n").unwrap());
+        assert_eq!(msg.direction(), None);
+        let msg = UsiMessage::from(EngineMessage::parse("yoho\n").unwrap());
+        assert_eq!(msg.direction(), None);
+
+        // one collection for both sides of a conversation
+        let log: Vec<UsiMessage> = vec![
+            GuiMessage::Usi.into(),
+            EngineMessage::UsiOk.into(),
+            GuiMessage::Quit.into(),
+        ];
+        let directions: Vec<_> = log.iter().filter_map(UsiMessage::direction).collect();
+        assert_eq!(
+            directions,
+            [
+                Direction::GuiToEngine,
+                Direction::EngineToGui,
+                Direction::GuiToEngine
+            ]
+        );
+    }
+
+    //
+    // line endings
+    //
+
+    #[test]
+    fn test_line_endings() {
+        let msg = GuiMessage::parse("go btime 1000\n").unwrap();
+        for (line_ending, expected) in [
+            (LineEnding::Lf, "go btime 1000\n"),
+            (LineEnding::CrLf, "go btime 1000\r\n"),
+            (LineEnding::Cr, "go btime 1000\r"),
+        ] {
+            assert_eq!(msg.to_usi_line_with(line_ending), expected);
+            let mut out = Vec::new();
+            msg.write_usi(&mut out, line_ending).unwrap();
+            assert_eq!(out, expected.as_bytes());
+            // and the parser accepts it back
+            assert_eq!(GuiMessage::parse(expected).unwrap(), msg);
+        }
+        assert_eq!(
+            msg.to_usi_line(),
+            msg.to_usi_line_with(LineEnding::default())
+        );
+
+        let mut writer = MessageWriter::new().line_ending(LineEnding::CrLf);
+        writer.extend([EngineMessage::UsiOk, EngineMessage::ReadyOk]);
+        assert_eq!(writer.as_str(), "usiok\r\nreadyok\r\n");
+        let msgs: Vec<EngineMessage> = EngineMessageStream::new(writer.as_str()).collect();
+        assert_eq!(msgs, vec![EngineMessage::UsiOk, EngineMessage::ReadyOk]);
+    }
+
+    //
+    // tsume
+    //
+
+    #[test]
+    fn test_go_mate_checkmate() {
+        let go = GuiMessage::parse("go mate 5000\n").unwrap();
+        assert_eq!(
+            go,
+            GuiMessage::Go(
+                EngineParams::new().mate(MateParam::Timeout(Duration::from_millis(5000)))
+            )
+        );
+
+        let replies = [
+            ("checkmate nomate\n", CheckMateParams::NoMate),
+            ("checkmate timeout\n", CheckMateParams::TimeOut),
+            (
+                "checkmate notimplemented\n",
+                CheckMateParams::NotImplemented,
+            ),
+            (
+                "checkmate G*5b 5a5b S*6c 5b6a 6c7b+\n",
+                CheckMateParams::Mate("G*5b 5a5b S*6c 5b6a 6c7b+".parse().unwrap()),
+            ),
+        ];
+        for (input, expected) in replies {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert_eq!(msg, EngineMessage::CheckMate(expected), "{input}");
+            assert!(msg.is_checkmate());
+            assert_eq!(msg.to_usi_line(), input);
+        }
+    }
+
+    #[test]
+    fn test_go_mate_convenience() {
+        for (input, params) in [
+            ("go mate 5000\n", EngineParams::new().mate_ms(5000)),
+            ("go mate infinite\n", EngineParams::new().mate_infinite()),
+        ] {
+            let msg = GuiMessage::parse(input).unwrap();
+            assert_eq!(msg.to_usi_line(), input);
+            let GuiMessage::Go(parsed) = msg else {
+                panic!("expected go");
+            };
+            assert_eq!(parsed, params);
+            assert_eq!(parsed.mate_param(), params.mate_param());
+        }
+        assert_eq!(
+            EngineParams::new().mate_ms(5000).mate_param(),
+            Some(&MateParam::Timeout(Duration::from_secs(5)))
+        );
+        assert_eq!(
+            EngineParams::new().mate_infinite().mate_param(),
+            Some(&MateParam::Infinite)
+        );
+        assert_eq!(EngineParams::new().depth(3).mate_param(), None);
+    }
+
+    #[test]
+    fn test_checkmate_invalid() {
+        for input in ["checkmate\n", "checkmate mate\n", "checkmate nomate 7g7f\n"] {
+            let msg = EngineMessage::parse(input).unwrap();
+            assert!(!msg.is_checkmate(), "{input}");
+        }
+    }
+
+    //
+    // option widgets
+    //
+
+    fn widget(line: &str) -> OptionWidget {
+        let msg = EngineMessage::parse(line).unwrap();
+        let EngineMessage::Option(param) = msg else {
+            panic!("expected option, got {msg:?}");
+        };
+        OptionWidget::from(&param)
+    }
+
+    #[test]
+    fn test_option_widget() {
+        let cases = [
+            (
+                "option name USI_Ponder type check default true\n",
+                "USI_Ponder",
+                WidgetControl::Toggle {
+                    default: Some(true),
+                },
+            ),
+            (
+                "option name Selectivity type spin default 2 min 0 max 4\n",
+                "Selectivity",
+                WidgetControl::Slider {
+                    default: Some(2),
+                    min: Some(0),
+                    max: Some(4),
+                },
+            ),
+            (
+                "option name Style type combo default Normal var Solid var Normal var Wild\n",
+                "Style",
+                WidgetControl::Dropdown {
+                    default: Some(s("Normal")),
+                    choices: vec![s("Solid"), s("Normal"), s("Wild")],
+                },
+            ),
+            (
+                "option name ClearHash type button\n",
+                "ClearHash",
+                WidgetControl::Button,
+            ),
+            (
+                "option name Greeting type string default Hello\n",
+                "Greeting",
+                WidgetControl::TextInput {
+                    default: Some(s("Hello")),
+                },
+            ),
+            (
+                "option name BookFile type filename default book.db\n",
+                "BookFile",
+                WidgetControl::FileInput {
+                    default: Some(s("book.db")),
+                },
+            ),
+        ];
+        for (line, label, control) in cases {
+            let widget = widget(line);
+            assert_eq!(widget.label, label, "{line}");
+            assert_eq!(widget.control, control, "{line}");
+        }
+    }
+
+    //
+    // eval series
+    //
+
+    #[test]
+    fn test_eval_series() {
+        // the input of test_engine_message_stream2
+        let input = "\
+        info depth 1 seldepth 0
+        info nps 1234567
+        info score cp 13  depth 1 nodes 13 time 15 pv 2g2f
+
+
+        info currmove 2g2f currmovenumber 1
+        info nodes 120000 nps 116391 hashfull 104
+        info string 7g7f (70%)
+        info score cp 156 multipv 1 pv P*5h 4g5g 5h5g 8b8f
+        ";
+        assert_eq!(eval_series(EngineMessageStream::new(input)), [(1, 13)]);
+
+        let input = "\
+info depth 2 score cp -40 pv 7g7f
+readyok
+info depth 3 score cp 12 upperbound
+info depth 9 score mate 7 pv 2g2f
+info depth 10 score mate -
+info depth 11 score mate -6
+";
+        assert_eq!(
+            eval_series(EngineMessageStream::new(input)),
+            [
+                (2, -40),
+                (3, 12),
+                (9, MATE_SCORE_CP - 7),
+                (10, -MATE_SCORE_CP),
+                (11, -MATE_SCORE_CP + 6)
+            ]
+        );
+        assert!(eval_series(Vec::new()).is_empty());
+
+        // long and out-of-range mates keep their sign
+        let input = "\
+info depth 1 score mate -99999999999
+info depth 2 score mate 99999999999
+info depth 3 score mate -40000
+info depth 4 score mate 40000
+info depth 5 score mate 0
+";
+        assert_eq!(
+            eval_series(EngineMessageStream::new(input)),
+            [(1, -1), (2, 1), (3, -1), (4, 1), (5, -MATE_SCORE_CP)]
+        );
+        for (n, key) in [
+            (i32::MIN, -1),
+            (i32::MAX, 1),
+            (MATE_SCORE_CP - 1, 1),
+            (-(MATE_SCORE_CP - 1), -1),
+            (1, MATE_SCORE_CP - 1),
+            (-1, -MATE_SCORE_CP + 1),
+        ] {
+            let param = InfoParam::ScoreMate(Some(n), ScoreBound::Exact);
+            assert_eq!(param.score_ordering_key(), Some(key), "mate {n}");
+        }
+    }
+
+    //
+    // pv positions
+    //
+
+    #[test]
+    fn test_pv_positions() {
+        let msg = EngineMessage::parse("info depth 3 score cp 40 pv 7g7f 3c3d 8h2b+\n").unwrap();
+        let EngineMessage::Info(info) = msg else {
+            panic!("expected info");
+        };
+        let sfens = pv_positions(&info, "startpos").unwrap();
+        assert_eq!(
+            sfens,
+            vec![
+                s("lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2"),
+                s("lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3"),
+                s("lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4"),
+            ]
+        );
+
+        // the base position can also be given as SFEN, and drops use the hand
+        let sfens = pv_positions(&info[..2], &sfens[2]);
+        assert_eq!(sfens, Ok(vec![]));
+        let info = vec![InfoParam::Pv(
+            vec!["4a3b".parse().unwrap(), "B*5e".parse().unwrap()].into(),
+        )];
+        let sfens = pv_positions(
+            &info,
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4",
+        )
+        .unwrap();
+        assert_eq!(
+            sfens[1],
+            "lnsgk1snl/1r4g+B1/pppppp1pp/6p2/4B4/2P6/PP1PPPPPP/7R1/LNSGKGSNL w - 6"
+        );
+    }
+
+    #[test]
+    fn test_pv_positions_illegal() {
+        let moves: Vec<Move> = ["7g7f", "3c3d", "7g7f"]
+            .iter()
+            .map(|m| m.parse().unwrap())
+            .collect();
+        let info = vec![InfoParam::Depth(3), InfoParam::Pv(moves.clone().into())];
+        assert_eq!(
+            pv_positions(&info, SFEN_STARTPOS),
+            Err(PositionError::IllegalMove {
+                index: 2,
+                mv: moves[2]
+            })
+        );
+
+        // moving the opponent's piece, capturing an own piece, and nifu
+        for mv in ["3c3d", "2h2g", "P*5e"] {
+            let info = vec![InfoParam::Pv(mv.parse().unwrap())];
+            assert!(matches!(
+                pv_positions(&info, "startpos"),
+                Err(PositionError::IllegalMove { index: 0, .. })
+            ));
+        }
+
+        assert!(matches!(
+            pv_positions(&info, "lnsgkgsnl/9 b - 1"),
+            Err(PositionError::InvalidSfen(_))
+        ));
+    }
+
+    #[test]
+    fn test_sfen_max_move_number() {
+        let msg = GuiMessage::parse(
+            "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 4294967295 moves 7g7f\n",
+        )
+        .unwrap();
+        let expected = "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 4294967295";
+        assert_eq!(msg.resulting_sfen().unwrap(), expected);
+        assert_eq!(msg.position_trace().unwrap()[0].1, expected);
+        assert_eq!(msg.position_eq(&msg), Ok(true));
+        assert_eq!(PositionCache::new().resulting_sfen(&msg).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_sfen_hand_counts() {
+        for hands in ["18P2B4L", "18p2r4g", "R2B3G4S4N4L18Pr"] {
+            let sfen = format!("4k4/9/9/9/9/9/9/9/4K4 b {hands} 1");
+            assert!(Sfen::new(&sfen).is_ok(), "{hands}");
+        }
+        // more pieces than a full set, including counts which overflow a u8
+        for hands in ["19P", "3B", "5G", "2P17P", "200P100P", "255P1P"] {
+            let sfen = format!("4k4/9/9/9/9/9/9/9/4K4 b {hands} 1");
+            assert_eq!(
+                Sfen::new(&sfen),
+                Err(PositionError::InvalidSfen(sfen.clone()))
+            );
+            assert_eq!(
+                GuiMessage::from_sfen(&sfen),
+                Err(ParseError::InvalidSfen(sfen.clone()))
+            );
+        }
+    }
 }
@@ -0,0 +1,59 @@
+//! This module contains [`UsiMessage`], which holds a message of either direction.
+//!
+//! This is convenient for code that handles both sides of a conversation, like a
+//! proxy or a log reader, and wants to keep the messages in one collection.
+use crate::engine::EngineMessage;
+use crate::gui::GuiMessage;
+
+/// A protocol message, tagged with its direction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UsiMessage {
+    /// A message sent by the GUI to the engine.
+    UsiGuiToEngine(GuiMessage),
+
+    /// A message sent by the engine to the GUI.
+    UsiEngineToGui(EngineMessage),
+}
+
+/// The direction of a [`UsiMessage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by the GUI to the engine.
+    GuiToEngine,
+
+    /// Sent by the engine to the GUI.
+    EngineToGui,
+}
+
+impl UsiMessage {
+    /// The direction of the message.
+    ///
+    /// Returns `None` if the wrapped message is `Unknown`, since text that could not
+    /// be parsed is not known to be a message of either direction.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let msg = UsiMessage::from(GuiMessage::IsReady);
+    /// assert_eq!(msg.direction(), Some(Direction::GuiToEngine));
+    /// ```
+    pub fn direction(&self) -> Option<Direction> {
+        match self {
+            Self::UsiGuiToEngine(GuiMessage::Unknown(_)) => None,
+            Self::UsiEngineToGui(EngineMessage::Unknown(_)) => None,
+            Self::UsiGuiToEngine(_) => Some(Direction::GuiToEngine),
+            Self::UsiEngineToGui(_) => Some(Direction::EngineToGui),
+        }
+    }
+}
+
+impl From<GuiMessage> for UsiMessage {
+    fn from(msg: GuiMessage) -> Self {
+        Self::UsiGuiToEngine(msg)
+    }
+}
+
+impl From<EngineMessage> for UsiMessage {
+    fn from(msg: EngineMessage) -> Self {
+        Self::UsiEngineToGui(msg)
+    }
+}
@@ -0,0 +1,92 @@
+//! This module implements [`OptionWidget`], a UI oriented description of an engine
+//! option.
+//!
+//! A GUI that renders the options of an engine needs the same information for every
+//! option: a label, the kind of control, the default and the constraints. Converting
+//! an [`OptionParam`] into an [`OptionWidget`] keeps the UI code independent of the
+//! protocol enum.
+use crate::engine::OptionParam;
+
+/// The control used to edit an engine option.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WidgetControl {
+    /// An on/off switch, for a `check` option.
+    Toggle { default: Option<bool> },
+
+    /// A slider, for a `spin` option.
+    Slider {
+        default: Option<i32>,
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+
+    /// A list of choices, for a `combo` option.
+    Dropdown {
+        default: Option<String>,
+        choices: Vec<String>,
+    },
+
+    /// A push button, for a `button` option.
+    Button,
+
+    /// A free text field, for a `string` option.
+    TextInput { default: Option<String> },
+
+    /// A file chooser, for a `filename` option.
+    FileInput { default: Option<String> },
+}
+
+/// Describes how to render an engine option.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let msg = EngineMessage::parse("option name Threads type spin default 1 min 1 max 64\n").unwrap();
+/// let EngineMessage::Option(param) = msg else { panic!() };
+/// let widget = OptionWidget::from(&param);
+/// assert_eq!(widget.label, "Threads");
+/// assert_eq!(
+///     widget.control,
+///     WidgetControl::Slider { default: Some(1), min: Some(1), max: Some(64) }
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OptionWidget {
+    /// The label of the control. This is the option name, which is also the name to
+    /// use in "setoption".
+    pub label: String,
+
+    /// The control, with its default and constraints.
+    pub control: WidgetControl,
+}
+
+impl From<&OptionParam> for OptionWidget {
+    fn from(param: &OptionParam) -> Self {
+        let control = match param {
+            OptionParam::Check { default, .. } => WidgetControl::Toggle { default: *default },
+            OptionParam::Spin {
+                default, min, max, ..
+            } => WidgetControl::Slider {
+                default: *default,
+                min: *min,
+                max: *max,
+            },
+            OptionParam::Combo { default, vars, .. } => WidgetControl::Dropdown {
+                default: default.clone(),
+                choices: vars.clone(),
+            },
+            OptionParam::Button { .. } => WidgetControl::Button,
+            OptionParam::String { default, .. } => WidgetControl::TextInput {
+                default: default.clone(),
+            },
+            OptionParam::Filename { default, .. } => WidgetControl::FileInput {
+                default: default.clone(),
+            },
+        };
+        OptionWidget {
+            label: param.name().to_string(),
+            control,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! This module implements a writer for blocks of protocol messages.
+//!
+//! A [`MessageWriter`] accumulates protocol lines in a `String`. Lines are terminated
+//! by `\n`, unless another [`LineEnding`] is chosen.
+//! This is convenient for building multi-line responses like the `id`/`option`/`usiok`
+//! block an engine sends in reply to `usi`, for which [`HandshakeResponse`] is a shortcut.
+use crate::engine::{EngineMessage, IdParams, OptionParam};
+use crate::gui::GuiMessage;
+use std::fmt;
+use std::io::{self, Write};
+
+/// The line terminator written after each message.
+///
+/// The parser accepts all three. Some peers, typically on Windows, expect `\r\n`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r`
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Accumulates serialized messages, one protocol line per message.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let mut writer = MessageWriter::new();
+/// writer.extend([
+///     EngineMessage::Id(IdParams::Name("haitaka".to_string())),
+///     EngineMessage::UsiOk,
+/// ]);
+/// assert_eq!(writer.into_string(), "id name haitaka\nusiok\n");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MessageWriter {
+    buf: String,
+    line_ending: LineEnding,
+}
+
+impl MessageWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Terminate the lines with this line ending. Default: [`LineEnding::Lf`].
+    #[must_use]
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// The text written so far.
+    pub fn as_str(&self) -> &str {
+        &self.buf
+    }
+
+    /// Consume the writer and return the text written so far.
+    pub fn into_string(self) -> String {
+        self.buf
+    }
+}
+
+impl Extend<GuiMessage> for MessageWriter {
+    fn extend<T: IntoIterator<Item = GuiMessage>>(&mut self, iter: T) {
+        for msg in iter {
+            self.buf += &msg.to_usi_line_with(self.line_ending);
+        }
+    }
+}
+
+impl Extend<EngineMessage> for MessageWriter {
+    fn extend<T: IntoIterator<Item = EngineMessage>>(&mut self, iter: T) {
+        for msg in iter {
+            self.buf += &msg.to_usi_line_with(self.line_ending);
+        }
+    }
+}
+
+/// The response of an engine to the `usi` command.
+///
+/// This writes the `id name`, `id author`, `option` and `usiok` lines, in the order
+/// required by the spec.
+///
+/// # Examples
+///
+/// ```
+/// use haitaka_usi::*;
+/// let response = HandshakeResponse {
+///     name: "haitaka".to_string(),
+///     author: "tofutofu".to_string(),
+///     options: vec![OptionParam::Button { name: "Clear Hash".to_string() }],
+/// };
+/// let mut out = Vec::new();
+/// response.write_to(&mut out).unwrap();
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     "id name haitaka\nid author tofutofu\noption name Clear Hash type button\nusiok\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HandshakeResponse {
+    pub name: String,
+    pub author: String,
+    pub options: Vec<OptionParam>,
+}
+
+impl HandshakeResponse {
+    /// The messages of the response, ending with `usiok`.
+    pub fn messages(&self) -> Vec<EngineMessage> {
+        let mut msgs = Vec::with_capacity(self.options.len() + 3);
+        msgs.push(EngineMessage::Id(IdParams::Name(self.name.clone())));
+        msgs.push(EngineMessage::Id(IdParams::Author(self.author.clone())));
+        msgs.extend(self.options.iter().cloned().map(EngineMessage::Option));
+        msgs.push(EngineMessage::UsiOk);
+        msgs
+    }
+
+    /// Write the response as one block of protocol lines.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut writer = MessageWriter::new();
+        writer.extend(self.messages());
+        w.write_all(writer.as_str().as_bytes())
+    }
+}
+
+impl GuiMessage {
+    /// Write the message as one protocol line with the given line ending.
+    pub fn write_usi<W: Write>(&self, w: &mut W, line_ending: LineEnding) -> io::Result<()> {
+        write!(w, "{}{}", self, line_ending)
+    }
+}
+
+impl EngineMessage {
+    /// Write the message as one protocol line with the given line ending.
+    ///
+    /// ```
+    /// use haitaka_usi::*;
+    /// let mut out = Vec::new();
+    /// EngineMessage::ReadyOk.write_usi(&mut out, LineEnding::CrLf).unwrap();
+    /// assert_eq!(out, b"readyok\r\n");
+    /// ```
+    pub fn write_usi<W: Write>(&self, w: &mut W, line_ending: LineEnding) -> io::Result<()> {
+        write!(w, "{}{}", self, line_ending)
+    }
+}